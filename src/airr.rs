@@ -0,0 +1,130 @@
+use crate::reader::open_reader;
+use crate::{PcaTreeError, SeqRecord};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Which columns of an AIRR / Change-O rearrangement table to use.
+#[derive(Debug, Clone)]
+pub struct AirrOptions {
+    /// Column holding the sequence, e.g. `sequence_alignment`, `cdr3` or `junction`.
+    pub seq_column: String,
+    /// Column holding the sequence name.
+    pub id_column: String,
+    /// Optional column to split the table by, e.g. `clone_id`.
+    pub group_column: Option<String>,
+    /// Optional column with the aligned germline, e.g. `germline_alignment`.
+    pub germline_column: Option<String>,
+}
+
+impl Default for AirrOptions {
+    fn default() -> Self {
+        Self {
+            seq_column: "sequence_alignment".to_string(),
+            id_column: "sequence_id".to_string(),
+            group_column: None,
+            germline_column: None,
+        }
+    }
+}
+
+/// All records sharing one value of the group column.
+#[derive(Debug, Clone)]
+pub struct SeqGroup {
+    pub name: String,
+    pub records: Vec<SeqRecord>,
+    /// First non-empty germline of the group, if a germline column was requested.
+    pub germline: Option<String>,
+}
+
+/// Read an AIRR rearrangement TSV (plain or gzipped).
+///
+/// Rows with an empty sequence are skipped. Without a group column all rows
+/// end up in one group called `all`. Groups keep the order of first appearance.
+pub fn read_airr<P: AsRef<Path>>(path: P, opts: &AirrOptions) -> Result<Vec<SeqGroup>, PcaTreeError> {
+    parse_airr(open_reader(path)?, opts)
+}
+
+/// Parse an AIRR rearrangement table from any buffered reader.
+pub fn parse_airr<R: BufRead>(reader: R, opts: &AirrOptions) -> Result<Vec<SeqGroup>, PcaTreeError> {
+    let mut lines = reader.lines();
+
+    let header = match lines.next() {
+        Some(h) => h?,
+        None => return Err(PcaTreeError::InvalidFormat("Empty AIRR table".to_string())),
+    };
+    let header: Vec<&str> = header.trim_end().split('\t').collect();
+
+    let column = |name: &str| -> Result<usize, PcaTreeError> {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| PcaTreeError::MissingColumn(name.to_string()))
+    };
+
+    let seq_col = column(&opts.seq_column)?;
+    let id_col = column(&opts.id_column)?;
+    let group_col = match &opts.group_column {
+        Some(g) => Some(column(g)?),
+        None => None,
+    };
+    let germline_col = match &opts.germline_column {
+        Some(g) => Some(column(g)?),
+        None => None,
+    };
+
+    let mut groups: Vec<SeqGroup> = Vec::new();
+    let mut index = HashMap::<String, usize>::new();
+
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let field = |c: usize| -> Result<&str, PcaTreeError> {
+            fields.get(c).copied().ok_or_else(|| {
+                PcaTreeError::InvalidFormat(format!(
+                    "AIRR row {} has only {} columns", i + 2, fields.len()
+                ))
+            })
+        };
+
+        let seq = field(seq_col)?;
+        if seq.is_empty() {
+            continue;
+        }
+        let rec = SeqRecord::new(field(id_col)?, seq);
+
+        let name = match group_col {
+            Some(c) => field(c)?,
+            None => "all",
+        };
+
+        let germline = match germline_col {
+            Some(c) => Some(field(c)?).filter(|g| !g.is_empty()),
+            None => None,
+        };
+
+        let g = match index.get(name) {
+            Some(&g) => g,
+            None => {
+                index.insert(name.to_string(), groups.len());
+                groups.push(SeqGroup {
+                    name: name.to_string(),
+                    records: Vec::new(),
+                    germline: None,
+                });
+                groups.len() - 1
+            }
+        };
+        groups[g].records.push(rec);
+        if groups[g].germline.is_none() {
+            groups[g].germline = germline.map(str::to_string);
+        }
+    }
+
+    Ok(groups)
+}
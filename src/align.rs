@@ -0,0 +1,239 @@
+use crate::PcaTreeError;
+use serde::{Deserialize, Serialize};
+
+/// Global (Needleman–Wunsch) alignment of unaligned sequences to one reference.
+///
+/// Every sequence is aligned pairwise to the reference and the insertions of all
+/// sequences are merged into one gapped multiple alignment (star alignment).
+/// Without an explicit reference the sequence of median length is used first, then
+/// everything is realigned against the consensus of that first alignment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aligner {
+    pub match_score: i32,
+    pub mismatch_score: i32,
+    /// Linear gap penalty per gap position (negative).
+    pub gap_score: i32,
+    /// Align against this sequence instead of the automatic consensus.
+    pub reference: Option<String>,
+}
+
+impl Default for Aligner {
+    fn default() -> Self {
+        Self {
+            match_score: 1,
+            mismatch_score: -1,
+            gap_score: -2,
+            reference: None,
+        }
+    }
+}
+
+impl Aligner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Align against a fixed reference, e.g. a germline sequence.
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    /// Align all sequences into equal-length gapped sequences (`-` for gaps).
+    pub fn align(&self, sequences: &[String]) -> Result<Vec<String>, PcaTreeError> {
+        if sequences.is_empty() {
+            return Err(PcaTreeError::EmptyInput);
+        }
+
+        // existing gaps are dropped, the input is treated as unaligned
+        let seqs: Vec<Vec<u8>> = sequences.iter().map(|s| ungapped(s)).collect();
+
+        let reference = match &self.reference {
+            Some(r) if r.is_empty() => return Err(PcaTreeError::EmptyInput),
+            Some(r) => r.to_ascii_uppercase().into_bytes(),
+            None => {
+                let mut by_len: Vec<&Vec<u8>> = seqs.iter().collect();
+                by_len.sort_by_key(|s| s.len());
+                let first = self.star(by_len[by_len.len() / 2], &seqs);
+                gapped_consensus(&first)
+            }
+        };
+
+        Ok(self
+            .star(&reference, &seqs)
+            .into_iter()
+            .map(|s| String::from_utf8_lossy(&s).into_owned())
+            .collect())
+    }
+
+    /// Align every sequence into the columns of an existing alignment of `width` columns,
+    /// given by its [`consensus_columns`]. Bases inserted relative to the consensus are
+    /// dropped and the consensus' gap columns stay gaps, so the alignment is unchanged.
+    pub(crate) fn align_into(&self, columns: &[(usize, u8)], width: usize, sequences: &[String]) -> Vec<String> {
+        let reference: Vec<u8> = columns.iter().map(|(_, b)| *b).collect();
+
+        sequences
+            .iter()
+            .map(|s| {
+                let mut out = vec![b'-'; width];
+                let (ra, sa) = self.pairwise(&reference, &ungapped(s));
+                let mut j = 0;
+                for (r, c) in ra.iter().zip(sa.iter()) {
+                    if *r != b'-' {
+                        out[columns[j].0] = *c;
+                        j += 1;
+                    }
+                }
+                String::from_utf8_lossy(&out).into_owned()
+            })
+            .collect()
+    }
+
+    /// Pairwise global alignment; returns both sequences with `-` inserted.
+    pub fn pairwise(&self, a: &[u8], b: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let (n, m) = (a.len(), b.len());
+        let mut score = vec![vec![0i32; m + 1]; n + 1];
+
+        for (i, row) in score.iter_mut().enumerate() {
+            row[0] = i as i32 * self.gap_score;
+        }
+        for (j, v) in score[0].iter_mut().enumerate() {
+            *v = j as i32 * self.gap_score;
+        }
+
+        for i in 1..=n {
+            for j in 1..=m {
+                let diag = score[i - 1][j - 1] + self.substitution(a[i - 1], b[j - 1]);
+                let up = score[i - 1][j] + self.gap_score;
+                let left = score[i][j - 1] + self.gap_score;
+                score[i][j] = diag.max(up).max(left);
+            }
+        }
+
+        // traceback, preferring diagonal moves
+        let (mut i, mut j) = (n, m);
+        let mut out_a = Vec::with_capacity(n.max(m));
+        let mut out_b = Vec::with_capacity(n.max(m));
+
+        while i > 0 || j > 0 {
+            if i > 0 && j > 0
+                && score[i][j] == score[i - 1][j - 1] + self.substitution(a[i - 1], b[j - 1])
+            {
+                out_a.push(a[i - 1]);
+                out_b.push(b[j - 1]);
+                i -= 1;
+                j -= 1;
+            } else if i > 0 && score[i][j] == score[i - 1][j] + self.gap_score {
+                out_a.push(a[i - 1]);
+                out_b.push(b'-');
+                i -= 1;
+            } else {
+                out_a.push(b'-');
+                out_b.push(b[j - 1]);
+                j -= 1;
+            }
+        }
+
+        out_a.reverse();
+        out_b.reverse();
+        (out_a, out_b)
+    }
+
+    fn substitution(&self, x: u8, y: u8) -> i32 {
+        if x == b'N' || y == b'N' {
+            0
+        } else if x == y {
+            self.match_score
+        } else {
+            self.mismatch_score
+        }
+    }
+
+    /// Align every sequence to `reference` and merge the insertions into one MSA.
+    fn star(&self, reference: &[u8], seqs: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let l = reference.len();
+
+        // per sequence: bases inserted before reference position j, and the base at j
+        let mut inserts: Vec<Vec<Vec<u8>>> = Vec::with_capacity(seqs.len());
+        let mut at: Vec<Vec<u8>> = Vec::with_capacity(seqs.len());
+        let mut max_ins = vec![0usize; l + 1];
+
+        for s in seqs {
+            let (ra, sa) = self.pairwise(reference, s);
+            let mut ins = vec![Vec::new(); l + 1];
+            let mut cols = Vec::with_capacity(l);
+            let mut j = 0;
+
+            for (r, c) in ra.iter().zip(sa.iter()) {
+                if *r == b'-' {
+                    ins[j].push(*c);
+                } else {
+                    cols.push(*c);
+                    j += 1;
+                }
+            }
+
+            for (m, i) in max_ins.iter_mut().zip(ins.iter()) {
+                *m = (*m).max(i.len());
+            }
+            inserts.push(ins);
+            at.push(cols);
+        }
+
+        inserts
+            .iter()
+            .zip(at.iter())
+            .map(|(ins, cols)| {
+                let mut out = Vec::new();
+                for j in 0..=l {
+                    out.extend_from_slice(&ins[j]);
+                    out.extend(std::iter::repeat_n(b'-', max_ins[j] - ins[j].len()));
+                    if j < l {
+                        out.push(cols[j]);
+                    }
+                }
+                out
+            })
+            .collect()
+    }
+}
+
+/// Uppercase sequence without gap characters.
+fn ungapped(s: &str) -> Vec<u8> {
+    s.bytes()
+        .filter(|b| *b != b'-' && *b != b'.')
+        .map(|b| b.to_ascii_uppercase())
+        .collect()
+}
+
+/// Majority residue per column, dropping columns that are mostly gaps.
+fn gapped_consensus(msa: &[Vec<u8>]) -> Vec<u8> {
+    consensus_columns(msa).into_iter().map(|(_, b)| b).collect()
+}
+
+/// Column index and majority residue of every column that is not mostly gaps.
+pub(crate) fn consensus_columns(msa: &[Vec<u8>]) -> Vec<(usize, u8)> {
+    let width = msa.first().map_or(0, |s| s.len());
+    let mut out = Vec::with_capacity(width);
+
+    for col in 0..width {
+        let mut counts = [0usize; 256];
+        for s in msa {
+            counts[s[col] as usize] += 1;
+        }
+        if counts[b'-' as usize] * 2 > msa.len() {
+            continue;
+        }
+        counts[b'-' as usize] = 0;
+
+        // lowest byte value wins ties, so the result does not depend on input order
+        let (best, _) = counts
+            .iter()
+            .enumerate()
+            .max_by(|(ia, a), (ib, b)| a.cmp(b).then(ib.cmp(ia)))
+            .unwrap();
+        out.push((col, best as u8));
+    }
+
+    out
+}
@@ -1,19 +1,183 @@
-use clap::Parser;
-use pca_tree::PcaTree;
-use std::fs::read_to_string;
+use clap::{Parser, Subcommand, ValueEnum};
+use pca_tree::{
+    read_airr, read_sequences, write_fasta, AaProperties, Aligner, AirrOptions, Alphabet,
+    CodonEncoder, CodonFeatures, ComponentSelection, ConsensusRules, Encoding, GapColumns, KmerEncoder, MutationDetail, PcaTree, PcaTreeError,
+    PositionMask, QualityWeighting, RelativeEncoder, SeqGroup, SeqRecord, Solver, SubstitutionEncoder, TieBreak, DEFAULT_SEED,
+};
 use std::path::{Path, PathBuf};
 
 
 #[derive(Parser)]
-#[command(author, version, about)]
+#[command(author, version, about, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    fit: Option<Args>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fit PCA + tree and write the outputs (the same as without a subcommand)
+    Fit(Box<Args>),
+    /// Project sequences into a model saved with `--save`
+    Project(ProjectArgs),
+}
+
+#[derive(clap::Args)]
+struct ProjectArgs {
+    /// Model file written by `pca_tree fit --save`
+    model: String,
+
+    /// Sequence file: FASTA, FASTQ or one sequence per line (optionally gzipped)
+    input: String,
+
+    /// Output table with the coordinates and nearest node (default: <input>_projected.tsv)
+    #[arg(short, long)]
+    out: Option<String>,
+}
+
+#[derive(clap::Args)]
 struct Args {
-    /// Path to file with one DNA sequence per line
+    /// Sequence file: FASTA, FASTQ or one sequence per line (optionally gzipped)
     input: String,
 
-    /// Number of PCA components
+    /// Read the input as AIRR / Change-O rearrangement TSV
+    #[arg(long)]
+    airr: bool,
+
+    /// AIRR column holding the sequences (e.g. sequence_alignment, cdr3, junction)
+    #[arg(long, default_value = "sequence_alignment")]
+    seq_column: String,
+
+    /// AIRR column holding the sequence ids
+    #[arg(long, default_value = "sequence_id")]
+    id_column: String,
+
+    /// AIRR column to group by (e.g. clone_id); one PCA + tree is built per group
+    #[arg(long)]
+    group_by: Option<String>,
+
+    /// AIRR column with the aligned germline (e.g. germline_alignment), used per group
+    #[arg(long)]
+    germline_column: Option<String>,
+
+    /// Germline to encode against and root the tree at: a sequence or a FASTA file (first record)
+    #[arg(long)]
+    germline: Option<String>,
+
+    /// Skip groups with fewer sequences than this
+    #[arg(long, default_value_t = 1)]
+    min_group_size: usize,
+
+    /// Number of PCA components (ignored with --select-k)
     #[arg(short, long, default_value_t = 3)]
     k: usize,
 
+    /// Choose the number of components from the eigenvalue spectrum instead of -k
+    #[arg(long, value_enum)]
+    select_k: Option<SelectArg>,
+
+    /// Share of the variance to explain with --select-k variance
+    #[arg(long, default_value_t = 0.9)]
+    variance_target: f32,
+
+    /// Number of permuted copies of the data for --select-k parallel
+    #[arg(long, default_value_t = 20)]
+    permutations: usize,
+
+    /// How to compute the components: the randomized solver only finds the top -k and
+    /// is much faster on many features; auto picks it when k is small against the data.
+    /// randomized falls back to full when -k is not below min(sequences, features) or
+    /// with --select-k
+    #[arg(long, value_enum, default_value_t = SolverArg::Auto)]
+    solver: SolverArg,
+
+    /// Random seed for --select-k parallel and the randomized solver
+    #[arg(long, default_value_t = DEFAULT_SEED)]
+    seed: u64,
+
+    /// Align variable-length sequences (Needleman–Wunsch star alignment) before encoding
+    #[arg(long)]
+    align: bool,
+
+    /// Reference to align against: a sequence or a FASTA file (first record); implies --align
+    #[arg(long)]
+    reference: Option<String>,
+
+    /// Output FASTA with the aligned sequences (default: <input>_aligned.fa when aligning)
+    #[arg(long)]
+    alignment: Option<String>,
+
+    /// How sequences are turned into PCA features
+    #[arg(long, value_enum, default_value_t = EncodingArg::Relative)]
+    encoding: EncodingArg,
+
+    /// Reading frame offset (0, 1 or 2) for codon-aware encodings
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2))]
+    frame: u8,
+
+    /// k-mer size for --encoding kmer; above 8 only together with --kmer-hash
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=31))]
+    kmer_size: u8,
+
+    /// Count k-mers and their reverse complement together (--encoding kmer)
+    #[arg(long)]
+    canonical: bool,
+
+    /// Hash k-mers into this many columns (--encoding kmer)
+    #[arg(long)]
+    kmer_hash: Option<usize>,
+
+    /// Only use these alignment columns: 1-based ranges and IMGT regions, e.g. "cdr1,cdr2,cdr3" or "1-20,300-320"
+    #[arg(long)]
+    mask: Option<String>,
+
+    /// Drop the --mask columns instead of keeping them (e.g. primer regions)
+    #[arg(long)]
+    mask_exclude: bool,
+
+    /// FASTQ: mask bases below this Phred quality
+    #[arg(long, conflicts_with = "quality_weights")]
+    min_quality: Option<u8>,
+
+    /// FASTQ: weight every base by the probability that its call is correct
+    #[arg(long)]
+    quality_weights: bool,
+
+    /// Merge identical sequences into one node weighted by its abundance
+    #[arg(long)]
+    collapse: bool,
+
+    /// Output table with the ids merged into every node (default: <input>_members.tsv with --collapse)
+    #[arg(long)]
+    members: Option<String>,
+
+    /// Use a sparse feature matrix (saves memory for one-hot encodings of long sequences)
+    #[arg(long)]
+    sparse: bool,
+
+    /// Consensus for tied columns: the first residue in alphabet order, or an ambiguity code
+    #[arg(long, value_enum, default_value_t = TieArg::First)]
+    consensus_ties: TieArg,
+
+    /// Minimum fraction of residues the consensus needs in its column, else N / X
+    #[arg(long, default_value_t = 0.0, value_parser = fraction)]
+    min_support: f32,
+
+    /// Consensus of gap-majority columns: ignore the gaps, flag the column as gap, or drop it
+    #[arg(long, value_enum, default_value_t = GapArg::Ignore)]
+    gap_columns: GapArg,
+
+    /// Output FASTA with the per-column consensus of the encoded sequences
+    #[arg(long)]
+    consensus: Option<String>,
+
+    /// Output table with per-column counts, consensus, entropy and gap fraction
+    #[arg(long)]
+    column_stats: Option<String>,
+
     /// Output table with PCA coordinates
     #[arg(long)]
     coords: Option<String>,
@@ -22,6 +186,22 @@ struct Args {
     #[arg(long)]
     edges: Option<String>,
 
+    /// Output table with every eigenvalue and its (cumulative) share of the variance
+    #[arg(long)]
+    variance: Option<String>,
+
+    /// Sequence file to project into the fitted PCA space (same encoding, consensus and mask)
+    #[arg(long)]
+    project: Option<String>,
+
+    /// Output table with the projected sequences and their nearest node (default: <input>_projected.tsv)
+    #[arg(long)]
+    projected: Option<String>,
+
+    /// Save the fitted model (JSON, gzipped for *.gz) for `pca_tree project`
+    #[arg(long)]
+    save: Option<String>,
+
     /// Write PCA plot (PNG)
     #[arg(long)]
     plot_pca: Option<String>,
@@ -30,75 +210,544 @@ struct Args {
     #[arg(long)]
     plot_tree: Option<String>,
 
+    /// Write scree plot of the explained variance (PNG)
+    #[arg(long)]
+    plot_scree: Option<String>,
+
+}
+
+/// CLI names for [`Encoding`].
+#[derive(Clone, Copy, ValueEnum)]
+enum EncodingArg {
+    /// Nucleotide one-hot (A, C, G, T, gap; IUPAC codes as fractions)
+    OneHot,
+    /// Nucleotide match / mismatch / gap against the consensus
+    Relative,
+    /// Amino-acid one-hot (20 residues, gap, stop, X)
+    Aa,
+    /// Amino-acid match / mismatch / gap against the consensus
+    AaRelative,
+    /// 5 Atchley factors per residue
+    Atchley,
+    /// 10 Kidera factors per residue
+    Kidera,
+    /// BLOSUM62 row per residue
+    Blosum62,
+    /// Substituted base (A, C, G, T, gap) against the consensus
+    Substitution,
+    /// Transition / transversion / gap against the consensus
+    TsTv,
+    /// Synonymous / non-synonymous / gap against the consensus (see --frame)
+    SynNonsyn,
+    /// Translated residue per codon, one-hot (see --frame)
+    CodonAa,
+    /// Codon one-hot: 64 codons and gap (see --frame)
+    Codon,
+    /// Replacement / silent / gap per codon against the consensus (see --frame)
+    CodonRs,
+    /// Alignment-free k-mer frequencies (see --kmer-size, --canonical, --kmer-hash)
+    Kmer,
+}
+
+/// CLI names for [`TieBreak`].
+#[derive(Clone, Copy, ValueEnum)]
+enum TieArg {
+    First,
+    Ambiguity,
+}
+
+/// CLI names for [`ComponentSelection`].
+#[derive(Clone, Copy, ValueEnum)]
+enum SelectArg {
+    /// Fewest components reaching --variance-target of the variance
+    Variance,
+    /// Components explaining more than the broken-stick expectation
+    BrokenStick,
+    /// Eigenvalues above the Marchenko–Pastur noise edge
+    MarchenkoPastur,
+    /// Eigenvalues above those of column-permuted data (Horn's parallel analysis)
+    Parallel,
+}
+
+/// CLI names for [`Solver`].
+#[derive(Clone, Copy, ValueEnum)]
+enum SolverArg {
+    Auto,
+    Full,
+    Randomized,
+}
+
+/// CLI names for [`GapColumns`].
+#[derive(Clone, Copy, ValueEnum)]
+enum GapArg {
+    Ignore,
+    Flag,
+    Drop,
+}
+
+impl Args {
+    fn component_selection(&self) -> Option<ComponentSelection> {
+        Some(match self.select_k? {
+            SelectArg::Variance => ComponentSelection::Variance(self.variance_target),
+            SelectArg::BrokenStick => ComponentSelection::BrokenStick,
+            SelectArg::MarchenkoPastur => ComponentSelection::MarchenkoPastur,
+            SelectArg::Parallel => ComponentSelection::ParallelAnalysis {
+                permutations: self.permutations,
+                seed: self.seed,
+            },
+        })
+    }
+
+    fn solver(&self) -> Solver {
+        match self.solver {
+            SolverArg::Auto => Solver::Auto,
+            SolverArg::Full => Solver::Full,
+            SolverArg::Randomized => Solver::Randomized,
+        }
+    }
+
+    fn consensus_rules(&self) -> ConsensusRules {
+        ConsensusRules {
+            ties: match self.consensus_ties {
+                TieArg::First => TieBreak::First,
+                TieArg::Ambiguity => TieBreak::Ambiguity,
+            },
+            min_support: self.min_support,
+            gap_columns: match self.gap_columns {
+                GapArg::Ignore => GapColumns::Ignore,
+                GapArg::Flag => GapColumns::Flag,
+                GapArg::Drop => GapColumns::Drop,
+            },
+        }
+    }
+}
+
+impl EncodingArg {
+    /// Whether sequence columns are residues or nucleotides, for IMGT region positions.
+    fn alphabet(self) -> Alphabet {
+        match self {
+            EncodingArg::Aa
+            | EncodingArg::AaRelative
+            | EncodingArg::Atchley
+            | EncodingArg::Kidera
+            | EncodingArg::Blosum62 => Alphabet::AminoAcid,
+            _ => Alphabet::Nucleotide,
+        }
+    }
+
+    fn encoding(self, args: &Args) -> Result<Encoding, PcaTreeError> {
+        let frame = usize::from(args.frame);
+        Ok(match self {
+            EncodingArg::OneHot => Encoding::OneHot,
+            EncodingArg::Relative => Encoding::Relative,
+            EncodingArg::Aa => Encoding::AminoAcid,
+            EncodingArg::AaRelative => Encoding::AminoAcidRelative,
+            EncodingArg::Atchley => Encoding::AminoAcidProperties(AaProperties::Atchley),
+            EncodingArg::Kidera => Encoding::AminoAcidProperties(AaProperties::Kidera),
+            EncodingArg::Blosum62 => Encoding::AminoAcidProperties(AaProperties::Blosum62),
+            EncodingArg::Substitution => Encoding::Substitution(MutationDetail::Base),
+            EncodingArg::TsTv => Encoding::Substitution(MutationDetail::TransitionTransversion),
+            EncodingArg::SynNonsyn => Encoding::Substitution(MutationDetail::Codon { frame }),
+            EncodingArg::CodonAa => Encoding::Codon { frame, features: CodonFeatures::Residue },
+            EncodingArg::Codon => Encoding::Codon { frame, features: CodonFeatures::Codon },
+            EncodingArg::CodonRs => Encoding::Codon { frame, features: CodonFeatures::Mutations },
+            EncodingArg::Kmer => {
+                let mut k = KmerEncoder::new(args.kmer_size.into())?.canonical(args.canonical);
+                if let Some(dim) = args.kmer_hash {
+                    k = k.hashed(dim);
+                }
+                k.validate()?;
+                Encoding::Kmer(k)
+            }
+        })
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    match (cli.command, cli.fit) {
+        (Some(Command::Project(args)), _) => project(&args),
+        (Some(Command::Fit(args)), _) => fit(*args),
+        (None, Some(args)) => fit(args),
+        (None, None) => unreachable!("clap requires <INPUT> without a subcommand"),
+    }
+}
 
-    let args = Args::parse();   // ✅ parse ONCE
+/// Load a saved model and write the coordinates and nearest node of new sequences.
+fn project(args: &ProjectArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let model = PcaTree::load(&args.model)?;
+    let records = read_sequences(&args.input)?;
+    let ids: Vec<String> = records.iter().map(|r| r.id.clone()).collect();
+    let seqs: Vec<String> = records.into_iter().map(|r| r.seq).collect();
 
-    let input = read_to_string(&args.input)?;
+    let coords = model.transform(&seqs)?;
+    let out: PathBuf = match &args.out {
+        Some(user) => PathBuf::from(user),
+        None => default_output_path(&args.input, "_projected.tsv"),
+    };
+    model.projection_to_tsv(&out, &ids, &coords)?;
+    println!("Written projected sequences → {}", out.display());
+    Ok(())
+}
 
-    let seqs: Vec<String> = input
-        .lines()
-        .filter(|l| !l.trim().is_empty())
-        .map(|s| s.to_string())
-        .collect();
+fn fit(args: Args) -> Result<(), Box<dyn std::error::Error>> {
 
-    // ✅ NEW: constructor does all work
-    let model = PcaTree::new(seqs, args.k)?;
+    let groups: Vec<SeqGroup> = if args.airr {
+        let opts = AirrOptions {
+            seq_column: args.seq_column.clone(),
+            id_column: args.id_column.clone(),
+            group_column: args.group_by.clone(),
+            germline_column: args.germline_column.clone(),
+        };
+        read_airr(&args.input, &opts)?
+    } else {
+        if args.group_by.is_some() {
+            eprintln!("⚠️ --group-by is only used together with --airr");
+        }
+        vec![SeqGroup {
+            name: "all".to_string(),
+            records: read_sequences(&args.input)?,
+            germline: None,
+        }]
+    };
 
+    let aligner = if args.align || args.reference.is_some() {
+        let mut a = Aligner::new();
+        if let Some(r) = &args.reference {
+            a = a.with_reference(sequence_arg(r)?);
+        }
+        Some(a)
+    } else {
+        None
+    };
+
+    let germline = match &args.germline {
+        Some(g) => Some(sequence_arg(g)?),
+        None => None,
+    };
+
+    let projected = match &args.project {
+        Some(f) => read_sequences(f)?,
+        None => Vec::new(),
+    };
+    if args.projected.is_some() && args.project.is_none() {
+        eprintln!("⚠️ --projected is only used together with --project");
+    }
+
+    let mask = match &args.mask {
+        Some(spec) => {
+            let m = PositionMask::parse(spec, args.encoding.alphabet())?;
+            Some(if args.mask_exclude { m.invert() } else { m })
+        }
+        None => None,
+    };
+
+    let quality = match (args.min_quality, args.quality_weights) {
+        (Some(q), _) => Some(QualityWeighting::Threshold(q)),
+        (None, true) => Some(QualityWeighting::Probabilistic),
+        (None, false) => None,
+    };
+    let position_free = matches!(
+        args.encoding,
+        EncodingArg::Kmer | EncodingArg::Codon | EncodingArg::CodonAa | EncodingArg::CodonRs
+    );
+    if quality.is_some() && position_free {
+        eprintln!("⚠️ base qualities are not used with --encoding kmer / codon*");
+    }
+
+    // fail before reading anything else on e.g. an unusable k-mer size
+    let encoding = args.encoding.encoding(&args)?;
+
+    // only tag output files when the user asked for groups
+    let grouped = args.airr && args.group_by.is_some();
+    let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+    let tags = unique_tags(&names);
+
+    let fit_group = |group: SeqGroup, tag: Option<&str>| -> Result<(), Box<dyn std::error::Error>> {
+        let mut builder = PcaTree::builder().components(args.k).solver(args.solver()).seed(args.seed);
+        if let Some(rule) = args.component_selection() {
+            builder = builder.select_components(rule);
+        }
+        // the consensus flags reach every encoding that compares against a consensus
+        let rules = args.consensus_rules();
+        builder = match encoding {
+            Encoding::Relative | Encoding::AminoAcidRelative => {
+                builder.encoder(RelativeEncoder::new(args.encoding.alphabet()).with_consensus(rules))
+            }
+            Encoding::Substitution(detail) => builder.encoder(SubstitutionEncoder::new(detail).with_consensus(rules)),
+            Encoding::Codon { frame, features } => {
+                builder.encoder(CodonEncoder::new(frame, features).with_consensus(rules))
+            }
+            e => builder.encoding(e),
+        };
+        if let Some(a) = &aligner {
+            builder = builder.align(a.clone());
+        }
+        if let Some(m) = &mask {
+            builder = builder.mask(m.clone());
+        }
+        if let Some(q) = quality {
+            builder = builder.quality(q);
+        }
+        builder = builder.collapse_duplicates(args.collapse).sparse(args.sparse);
+        if let Some(g) = group.germline.or_else(|| germline.clone()) {
+            builder = builder.germline(g);
+        }
+        let model = builder.fit_records(group.records)?;
+
+        write_outputs(&args, &model, tag, &projected)
+    };
+
+    if !grouped {
+        for group in groups {
+            fit_group(group, None)?;
+        }
+        return Ok(());
+    }
+
+    // one failing clone should not cost the results of all others
+    let mut failed = Vec::new();
+    for (group, tag) in groups.into_iter().zip(&tags) {
+        if group.records.len() < args.min_group_size {
+            eprintln!(
+                "Skipping group {} with {} sequence(s)",
+                group.name,
+                group.records.len()
+            );
+            continue;
+        }
+        let name = group.name.clone();
+        if let Err(e) = fit_group(group, Some(tag)) {
+            eprintln!("⚠️ group {name} failed: {e}");
+            failed.push(name);
+        }
+    }
+    if !failed.is_empty() {
+        return Err(format!("{} group(s) failed: {}", failed.len(), failed.join(", ")).into());
+    }
+
+    Ok(())
+}
+
+
+/// Write tables and plots for one fitted model; `tag` is added to every file name.
+fn write_outputs(
+    args: &Args,
+    model: &PcaTree,
+    tag: Option<&str>,
+    projected: &[SeqRecord],
+) -> Result<(), Box<dyn std::error::Error>> {
+
+    let suffix = |s: &str| match tag {
+        Some(t) => format!("_{t}{s}"),
+        None => s.to_string(),
+    };
 
     // Determine PCA output path
     let coords_path: PathBuf = if let Some(user) = args.coords.as_ref() {
-        PathBuf::from(user)
+        tagged_path(user, tag)
     } else {
-        default_output_path(&args.input, "_pca.tsv")
+        default_output_path(&args.input, &suffix("_pca.tsv"))
     };
 
     // Determine MST edge output path
     let edges_path: PathBuf = if let Some(user) = args.edges.as_ref() {
-        PathBuf::from(user)
+        tagged_path(user, tag)
     } else {
-        default_output_path(&args.input, "_tree.tsv")
+        default_output_path(&args.input, &suffix("_tree.tsv"))
     };
 
-    model.pca.to_tsv(&coords_path)?;
+    if let Some(aln) = &model.alignment {
+        let aln_path: PathBuf = if let Some(user) = args.alignment.as_ref() {
+            tagged_path(user, tag)
+        } else {
+            default_output_path(&args.input, &suffix("_aligned.fa"))
+        };
+        write_fasta(&aln_path, model.ids(), aln)?;
+        println!("Written alignment → {}", aln_path.display());
+    }
+
+    if model.members.is_some() {
+        model.pca.to_tsv_counted(&coords_path, model.ids(), &model.counts)?;
+    } else {
+        model.pca.to_tsv_named(&coords_path, model.ids())?;
+    }
     println!("Written PCA coords → {}", coords_path.display());
     
-    model.tree.to_tsv(&edges_path)?;
+    if model.members.is_some() {
+        model.tree.to_tsv_counted(&edges_path, model.ids(), &model.counts)?;
+    } else {
+        model.tree.to_tsv_named(&edges_path, model.ids())?;
+    }
     println!("Written MSt edges → {}", edges_path.display());
+
+    if let Some(rule) = &model.pca.selection {
+        println!("Selected k = {} by {rule}", model.pca.n_components());
+    }
+    if let Some(explained) = model.pca.cumulative_variance().last() {
+        println!(
+            "{} components explain {:.1}% of the variance",
+            model.pca.n_components(),
+            explained * 100.0
+        );
+    }
+    if let Some(f) = &args.variance {
+        let f = tagged_path(f, tag);
+        model.pca.variance_to_tsv(&f)?;
+        println!("Written explained variance → {}", f.display());
+    }
+
+    if !projected.is_empty() {
+        let projected_path: PathBuf = if let Some(user) = args.projected.as_ref() {
+            tagged_path(user, tag)
+        } else {
+            default_output_path(&args.input, &suffix("_projected.tsv"))
+        };
+        let ids: Vec<String> = projected.iter().map(|r| r.id.clone()).collect();
+        let seqs: Vec<String> = projected.iter().map(|r| r.seq.clone()).collect();
+        let coords = model.transform(&seqs)?;
+        model.projection_to_tsv(&projected_path, &ids, &coords)?;
+        println!("Written projected sequences → {}", projected_path.display());
+    }
+
+    if let Some(f) = &args.save {
+        let f = tagged_path(f, tag);
+        model.save(&f)?;
+        println!("Written model → {}", f.display());
+    }
+
+    if args.consensus.is_some() || args.column_stats.is_some() {
+        let stats = model.column_stats(args.encoding.alphabet())?.with_rules(args.consensus_rules());
+        if let Some(f) = &args.consensus {
+            let f = tagged_path(f, tag);
+            stats.to_fasta(&f, tag.unwrap_or("consensus"))?;
+            println!("Written consensus → {}", f.display());
+        }
+        if let Some(f) = &args.column_stats {
+            let f = tagged_path(f, tag);
+            stats.to_tsv(&f)?;
+            println!("Written column stats → {}", f.display());
+        }
+    }
+
+    if model.members.is_some() || args.members.is_some() {
+        let members_path: PathBuf = if let Some(user) = args.members.as_ref() {
+            tagged_path(user, tag)
+        } else {
+            default_output_path(&args.input, &suffix("_members.tsv"))
+        };
+        model.members_to_tsv(&members_path)?;
+        println!("Written node members → {}", members_path.display());
+    }
     
 
     #[cfg(feature = "plot")]
     {
-        if let Some(f) = args.plot_pca {
+        if let Some(f) = &args.plot_pca {
+            let f = tagged_path(f, tag);
+            let f = f.to_string_lossy();
             model.pca.plot_2d_clusters( &model.tree, &f)?;
             eprintln!("✅ PCA plot written to {f}");
         }
 
-        if let Some(f) = args.plot_tree {
-            model.tree.plot_2d(&model.coords(), &f)?;
+        if let Some(f) = &args.plot_tree {
+            let f = tagged_path(f, tag);
+            let f = f.to_string_lossy();
+            model.tree.plot_2d(model.pca.coords(), &f)?;
             eprintln!("✅ Tree plot written to {f}");
         }
+
+        if let Some(f) = &args.plot_scree {
+            let f = tagged_path(f, tag);
+            let f = f.to_string_lossy();
+            model.pca.plot_scree(&f)?;
+            eprintln!("✅ Scree plot written to {f}");
+        }
     }
 
 
     #[cfg(not(feature = "plot"))]
     {
-        if args.plot_pca.is_some() || args.plot_tree.is_some() {
+        if args.plot_pca.is_some() || args.plot_tree.is_some() || args.plot_scree.is_some() {
             eprintln!("⚠️ Plotting is disabled. Recompile with: cargo build --features plot");
         }
     }
-    
 
     Ok(())
 }
 
+/// `--reference` / `--germline` are either a FASTA / plain sequence file or the sequence itself.
+fn sequence_arg(arg: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if !Path::new(arg).is_file() {
+        return Ok(arg.to_string());
+    }
+    match read_sequences(arg)?.into_iter().next() {
+        Some(rec) => Ok(rec.seq),
+        None => Err(format!("No sequence in file {arg}").into()),
+    }
+}
+
+/// Value parser for shares between 0 and 1.
+fn fraction(arg: &str) -> Result<f32, String> {
+    match arg.parse::<f32>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        Ok(v) => Err(format!("{v} is not between 0 and 1")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// out/pca.tsv + tag 12 -> out/pca_12.tsv
+fn tagged_path(user: &str, tag: Option<&str>) -> PathBuf {
+    let path = PathBuf::from(user);
+    let Some(tag) = tag else {
+        return path;
+    };
 
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, tag, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, tag),
+    };
+    path.with_file_name(name)
+}
+
+/// Keep group names usable as part of a file name.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+/// File name tags for the groups: sanitised names, with `-2`, `-3`, .. added where
+/// distinct names (e.g. `a/b` and `a_b`) would otherwise write to the same files.
+/// Compared case-insensitively, for case-insensitive file systems.
+fn unique_tags(names: &[&str]) -> Vec<String> {
+    let mut taken = std::collections::HashSet::new();
+    names
+        .iter()
+        .map(|name| {
+            let base = sanitize(name);
+            let mut tag = base.clone();
+            let mut i = 1;
+            while !taken.insert(tag.to_lowercase()) {
+                i += 1;
+                tag = format!("{base}-{i}");
+            }
+            if i > 1 {
+                eprintln!("⚠️ group {name} is written as {tag}, {base} is already taken");
+            }
+            tag
+        })
+        .collect()
+}
 
 fn default_output_path(input: &str, suffix: &str) -> PathBuf {
     let path = Path::new(input);
-    let stem = path.file_stem().unwrap_or_default();
+    let mut stem = path.file_stem().unwrap_or_default();
+    // seqs.fa.gz -> seqs
+    if path.extension().is_some_and(|e| e == "gz") {
+        stem = Path::new(stem).file_stem().unwrap_or(stem);
+    }
     let parent = path.parent().unwrap_or_else(|| Path::new("."));
 
     let mut out = PathBuf::new();
@@ -0,0 +1,267 @@
+use crate::encoder::{aligned_len, channel_names, iupac_weights, normalize_base};
+use crate::{Alphabet, AminoAcidEncoder, ColumnStats, ConsensusRules, PcaTreeError, SavedEncoder, SequenceEncoder};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+/// Standard genetic code, indexed by `16 * b1 + 4 * b2 + b3` with T = 0, C = 1, A = 2, G = 3.
+const GENETIC_CODE: &[u8; 64] =
+    b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+/// Translate one codon with the standard genetic code; `*` for stop codons.
+///
+/// Returns `None` for anything but three unambiguous bases (gaps, IUPAC codes).
+pub(crate) fn translate(codon: &[u8]) -> Option<u8> {
+    let [a, b, c] = codon else {
+        return None;
+    };
+    let idx = |x: &u8| match x.to_ascii_uppercase() {
+        b'T' | b'U' => Some(0),
+        b'C' => Some(1),
+        b'A' => Some(2),
+        b'G' => Some(3),
+        _ => None,
+    };
+    Some(GENETIC_CODE[16 * idx(a)? + 4 * idx(b)? + idx(c)?])
+}
+
+/// What [`CodonEncoder`] emits per codon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CodonFeatures {
+    /// The translated residue, one-hot like [`crate::AminoAcidEncoder`] (23 columns).
+    #[default]
+    Residue,
+    /// The codon itself: 64 codons (TTT, TTC, ..., GGG) and gap.
+    Codon,
+    /// Replacement, silent and gap flags against the consensus or a reference codon.
+    Mutations,
+}
+
+/// Encodes aligned nucleotide sequences codon by codon in a fixed reading frame.
+///
+/// Codons start at `frame` (0, 1 or 2); leading bases and a trailing partial codon are dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodonEncoder {
+    pub frame: usize,
+    pub features: CodonFeatures,
+    /// Compare against this sequence instead of the consensus (`Mutations` only).
+    pub reference: Option<String>,
+    /// How the consensus is chosen when there is no reference (`Mutations` only).
+    pub consensus: ConsensusRules,
+    /// Base positions whose codons are always 0, like the dropped gap columns of the
+    /// consensus this encoder was frozen at.
+    pub dropped: Vec<usize>,
+}
+
+impl CodonEncoder {
+    pub fn new(frame: usize, features: CodonFeatures) -> Self {
+        Self { frame, features, reference: None, consensus: ConsensusRules::default(), dropped: Vec::new() }
+    }
+
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    pub fn with_consensus(mut self, rules: ConsensusRules) -> Self {
+        self.consensus = rules;
+        self
+    }
+
+    /// Number of complete codons in a sequence of length `len`.
+    pub fn codons(&self, len: usize) -> usize {
+        len.saturating_sub(self.frame) / 3
+    }
+
+    /// Translate one sequence in the encoder's frame: `-` for all-gap codons,
+    /// `X` for codons with ambiguous bases or partial gaps.
+    pub fn translate(&self, seq: &str) -> String {
+        let bytes = seq.as_bytes();
+        (0..self.codons(bytes.len()))
+            .map(|c| codon_residue(&bytes[self.frame + 3 * c..self.frame + 3 * c + 3]) as char)
+            .collect()
+    }
+
+    /// Encode into `n × cC` features for `C` codons and the `c` channels of the chosen features.
+    ///
+    /// For `Mutations`, a codon differing from the reference counts as replacement if it
+    /// translates to another residue (or a stop) and as silent otherwise.
+    /// Codons with ambiguous bases on either side are left at 0.
+    pub fn encode_batch(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        if self.frame > 2 {
+            return Err(PcaTreeError::ReadingFrame(self.frame));
+        }
+        let len = aligned_len(sequences)?;
+
+        match self.features {
+            CodonFeatures::Residue => {
+                let residues: Vec<String> = sequences.iter().map(|s| self.translate(s)).collect();
+                if residues[0].is_empty() {
+                    return Ok(Array2::zeros((sequences.len(), 0)));
+                }
+                AminoAcidEncoder::new().encode_batch(&residues)
+            }
+            CodonFeatures::Codon => self.encode_codons(sequences, len),
+            CodonFeatures::Mutations => self.encode_mutations(sequences, len),
+        }
+    }
+
+    fn encode_codons(&self, sequences: &[String], len: usize) -> Result<Array2<f32>, PcaTreeError> {
+        let width = CODON_CHANNELS;
+        let mut x = Array2::<f32>::zeros((sequences.len(), width * self.codons(len)));
+
+        for (i, seq) in sequences.iter().enumerate() {
+            let bytes = seq.as_bytes();
+            for c in 0..self.codons(len) {
+                let start = self.frame + 3 * c;
+                let mut w = Vec::with_capacity(3);
+                for (pos, &b) in bytes[start..start + 3].iter().enumerate() {
+                    let Some(bw) = iupac_weights(b) else {
+                        return Err(PcaTreeError::InvalidSymbol {
+                            index: i,
+                            position: start + pos,
+                            symbol: b as char,
+                        });
+                    };
+                    w.push(bw);
+                }
+
+                if w.iter().any(|bw| bw[4] > 0.0) {
+                    x[[i, width * c + 64]] = 1.0;
+                    continue;
+                }
+                // IUPAC codes spread over every codon they can stand for
+                for (idx, code) in CODON_ORDER.iter().enumerate() {
+                    let v: f32 = (0..3).map(|k| w[k][nt_index(code[k])]).product();
+                    x[[i, width * c + idx]] = v;
+                }
+            }
+        }
+
+        Ok(x)
+    }
+
+    fn encode_mutations(&self, sequences: &[String], len: usize) -> Result<Array2<f32>, PcaTreeError> {
+        let (reference, dropped) = match &self.reference {
+            Some(r) if r.len() != len => {
+                return Err(PcaTreeError::ReferenceLength { expected: len, found: r.len() });
+            }
+            Some(r) => (r.to_ascii_uppercase().into_bytes(), self.dropped.clone()),
+            None => {
+                let stats = ColumnStats::new(sequences, Alphabet::Nucleotide)?.with_rules(self.consensus);
+                (stats.consensus().into_bytes(), stats.dropped_columns())
+            }
+        };
+
+        let mut x = Array2::<f32>::zeros((sequences.len(), 3 * self.codons(len)));
+
+        for (i, seq) in sequences.iter().enumerate() {
+            let bytes = seq.to_ascii_uppercase().into_bytes();
+            for (pos, &b) in bytes.iter().enumerate() {
+                if iupac_weights(b).is_none() {
+                    return Err(PcaTreeError::InvalidSymbol { index: i, position: pos, symbol: b as char });
+                }
+            }
+
+            for c in 0..self.codons(len) {
+                let start = self.frame + 3 * c;
+                let codon = &bytes[start..start + 3];
+                let germ = &reference[start..start + 3];
+
+                if (start..start + 3).any(|p| dropped.binary_search(&p).is_ok()) {
+                    continue;
+                }
+                if codon.iter().any(|b| matches!(b, b'-' | b'.')) {
+                    x[[i, 3 * c + 2]] = 1.0;
+                    continue;
+                }
+                let same = codon.iter().zip(germ).all(|(a, b)| normalize_base(*a) == normalize_base(*b));
+                if same {
+                    continue;
+                }
+                if let (Some(a), Some(b)) = (translate(codon), translate(germ)) {
+                    x[[i, 3 * c + usize::from(a == b)]] = 1.0;
+                }
+            }
+        }
+
+        Ok(x)
+    }
+}
+
+impl SequenceEncoder for CodonEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        self.encode_batch(sequences)
+    }
+
+    fn feature_names(&self, len: usize) -> Vec<String> {
+        let n = self.codons(len);
+        match self.features {
+            CodonFeatures::Residue => AminoAcidEncoder::new().feature_names(n),
+            CodonFeatures::Codon => {
+                let mut channels: Vec<String> = CODON_ORDER
+                    .iter()
+                    .map(|c| String::from_utf8_lossy(c).into_owned())
+                    .collect();
+                channels.push("gap".to_string());
+                channel_names(n, &channels)
+            }
+            CodonFeatures::Mutations => channel_names(n, &["R", "S", "gap"]),
+        }
+    }
+
+    fn with_reference(&self, reference: &str) -> Option<Box<dyn SequenceEncoder>> {
+        match self.features {
+            CodonFeatures::Mutations => Some(Box::new(self.clone().with_reference(reference))),
+            _ => None,
+        }
+    }
+
+    fn freeze(&self, sequences: &[String]) -> Result<Option<Box<dyn SequenceEncoder>>, PcaTreeError> {
+        if self.features != CodonFeatures::Mutations || self.reference.is_some() {
+            return Ok(None);
+        }
+        let stats = ColumnStats::new(sequences, Alphabet::Nucleotide)?.with_rules(self.consensus);
+        let frozen = CodonEncoder {
+            dropped: stats.dropped_columns(),
+            ..self.clone().with_reference(stats.consensus())
+        };
+        Ok(Some(Box::new(frozen)))
+    }
+
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::Codon(self.clone()))
+    }
+}
+
+/// 64 codons and gap per codon in [`CodonFeatures::Codon`].
+const CODON_CHANNELS: usize = 65;
+
+/// All codons in genetic code order (T, C, A, G per position).
+const CODON_ORDER: [[u8; 3]; 64] = {
+    const B: &[u8; 4] = b"TCAG";
+    let mut out = [[0u8; 3]; 64];
+    let mut i = 0;
+    while i < 64 {
+        out[i] = [B[i / 16], B[i / 4 % 4], B[i % 4]];
+        i += 1;
+    }
+    out
+};
+
+/// Column of a base in the (A, C, G, T, gap) IUPAC weights.
+fn nt_index(b: u8) -> usize {
+    match b {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        _ => 3,
+    }
+}
+
+fn codon_residue(codon: &[u8]) -> u8 {
+    if codon.iter().all(|b| matches!(b, b'-' | b'.')) {
+        b'-'
+    } else {
+        translate(codon).unwrap_or(b'X')
+    }
+}
@@ -0,0 +1,243 @@
+use crate::encoder::{aligned_len, normalize_base, AMINO_ACIDS};
+use crate::{Alphabet, PcaTreeError};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// How [`ColumnStats::consensus`] resolves ties between the most frequent residues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TieBreak {
+    /// The residue that comes first in the alphabet (A < C < G < T, amino acids A to Y).
+    #[default]
+    First,
+    /// The IUPAC code of the tied bases (e.g. `R` for A/G); for amino acids `B` (D/N),
+    /// `Z` (E/Q), `J` (I/L) or `X` otherwise.
+    Ambiguity,
+}
+
+/// What the consensus is in columns where most sequences have a gap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GapColumns {
+    /// Use the most frequent residue as in every other column.
+    #[default]
+    Ignore,
+    /// The consensus is a gap, so residues there count as mismatches.
+    Flag,
+    /// Leave the column out: relative encodings set it to 0 for every sequence,
+    /// so it adds no variance (the consensus shows a gap).
+    Drop,
+}
+
+/// Rules for the per-column consensus.
+///
+/// Columns without any residue (all gaps or ambiguity codes) get `N` / `X`,
+/// as does a column whose most frequent residue is below `min_support`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ConsensusRules {
+    pub ties: TieBreak,
+    /// Minimum fraction of the residues in a column the winner needs (0 to 1).
+    pub min_support: f32,
+    pub gap_columns: GapColumns,
+}
+
+/// Per-column symbol counts of an alignment, with the consensus the relative
+/// encodings compare against.
+///
+/// Residues are counted case-insensitively (`U` as `T`); gaps (`-`, `.`) and
+/// anything outside the alphabet (ambiguity codes, `X`, `*`) are counted separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    /// Counted symbols, the column order of `counts`.
+    pub symbols: Vec<u8>,
+    /// `counts[col][s]`: how often `symbols[s]` occurs in column `col`.
+    pub counts: Vec<Vec<usize>>,
+    /// Gaps per column.
+    pub gaps: Vec<usize>,
+    /// Other symbols per column.
+    pub other: Vec<usize>,
+    /// Number of sequences.
+    pub n: usize,
+    /// How the consensus is derived from the counts.
+    pub rules: ConsensusRules,
+}
+
+impl ColumnStats {
+    /// Count the columns of equal-length sequences.
+    pub fn new(sequences: &[String], alphabet: Alphabet) -> Result<Self, PcaTreeError> {
+        let symbols: &[u8] = match alphabet {
+            Alphabet::Nucleotide => b"ACGT",
+            Alphabet::AminoAcid => AMINO_ACIDS,
+        };
+        let len = aligned_len(sequences)?;
+
+        let mut counts = vec![vec![0; symbols.len()]; len];
+        let mut gaps = vec![0; len];
+        let mut other = vec![0; len];
+
+        for seq in sequences {
+            for (col, &b) in seq.as_bytes().iter().enumerate() {
+                let b = match alphabet {
+                    Alphabet::Nucleotide => normalize_base(b),
+                    Alphabet::AminoAcid => b.to_ascii_uppercase(),
+                };
+                match symbols.iter().position(|&s| s == b) {
+                    Some(s) => counts[col][s] += 1,
+                    None if b == b'-' || b == b'.' => gaps[col] += 1,
+                    None => other[col] += 1,
+                }
+            }
+        }
+
+        Ok(Self {
+            symbols: symbols.to_vec(),
+            counts,
+            gaps,
+            other,
+            n: sequences.len(),
+            rules: ConsensusRules::default(),
+        })
+    }
+
+    pub fn with_rules(mut self, rules: ConsensusRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Number of columns.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Consensus symbol per column following [`ColumnStats::rules`].
+    pub fn consensus(&self) -> String {
+        (0..self.len()).map(|col| self.column_consensus(col) as char).collect()
+    }
+
+    /// Whether more than half of the sequences have a gap in column `col`.
+    pub fn gap_majority(&self, col: usize) -> bool {
+        2 * self.gaps[col] > self.n
+    }
+
+    /// Columns the rules leave out of the relative encodings.
+    pub fn dropped(&self, col: usize) -> bool {
+        self.rules.gap_columns == GapColumns::Drop && self.gap_majority(col)
+    }
+
+    /// All columns for which [`ColumnStats::dropped`] holds, in increasing order.
+    pub fn dropped_columns(&self) -> Vec<usize> {
+        (0..self.len()).filter(|&c| self.dropped(c)).collect()
+    }
+
+    fn column_consensus(&self, col: usize) -> u8 {
+        let nucleotide = self.symbols.len() == 4;
+        let unknown = if nucleotide { b'N' } else { b'X' };
+
+        if self.gap_majority(col) && self.rules.gap_columns != GapColumns::Ignore {
+            return b'-';
+        }
+
+        let counts = &self.counts[col];
+        let total: usize = counts.iter().sum();
+        let best = counts.iter().copied().max().unwrap_or(0);
+        if best == 0 || (best as f32) < self.rules.min_support * total as f32 {
+            return unknown;
+        }
+
+        let tied: Vec<u8> = self
+            .symbols
+            .iter()
+            .zip(counts)
+            .filter(|(_, c)| **c == best)
+            .map(|(s, _)| *s)
+            .collect();
+
+        match (tied.as_slice(), self.rules.ties) {
+            ([only], _) | ([only, ..], TieBreak::First) => *only,
+            (tied, TieBreak::Ambiguity) if nucleotide => iupac_code(tied),
+            (b"DN", TieBreak::Ambiguity) => b'B',
+            (b"EQ", TieBreak::Ambiguity) => b'Z',
+            (b"IL", TieBreak::Ambiguity) => b'J',
+            _ => unknown,
+        }
+    }
+
+    /// Shannon entropy (bits) of the residues in column `col`, gaps and other symbols ignored.
+    pub fn entropy(&self, col: usize) -> f64 {
+        let total: usize = self.counts[col].iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        -self.counts[col]
+            .iter()
+            .filter(|c| **c > 0)
+            .map(|&c| {
+                let p = c as f64 / total as f64;
+                p * p.log2()
+            })
+            .sum::<f64>()
+    }
+
+    /// Fraction of sequences with a gap in column `col`.
+    pub fn gap_fraction(&self, col: usize) -> f64 {
+        if self.n == 0 { 0.0 } else { self.gaps[col] as f64 / self.n as f64 }
+    }
+
+    /// Write one line per column: position, consensus, symbol counts, gap and other counts,
+    /// entropy and gap fraction.
+    pub fn to_tsv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+
+        write!(w, "pos\tconsensus")?;
+        for s in &self.symbols {
+            write!(w, "\t{}", *s as char)?;
+        }
+        writeln!(w, "\tgap\tother\tentropy\tgap_fraction")?;
+
+        let consensus = self.consensus();
+        for (col, c) in consensus.chars().enumerate() {
+            write!(w, "{}\t{}", col + 1, c)?;
+            for n in &self.counts[col] {
+                write!(w, "\t{}", n)?;
+            }
+            writeln!(
+                w,
+                "\t{}\t{}\t{:.6}\t{:.6}",
+                self.gaps[col],
+                self.other[col],
+                self.entropy(col),
+                self.gap_fraction(col)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the consensus as a one-record FASTA file.
+    pub fn to_fasta<P: AsRef<Path>>(&self, path: P, id: &str) -> std::io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, ">{}", id)?;
+        writeln!(w, "{}", self.consensus())?;
+        Ok(())
+    }
+}
+
+/// IUPAC code for a sorted set of bases out of `ACGT`.
+fn iupac_code(bases: &[u8]) -> u8 {
+    match bases {
+        b"AG" => b'R',
+        b"CT" => b'Y',
+        b"CG" => b'S',
+        b"AT" => b'W',
+        b"GT" => b'K',
+        b"AC" => b'M',
+        b"CGT" => b'B',
+        b"AGT" => b'D',
+        b"ACT" => b'H',
+        b"ACG" => b'V',
+        _ => b'N',
+    }
+}
@@ -1,103 +1,593 @@
+use crate::{
+    AaProperties, ColumnStats, ConsensusRules, CsrMatrix, CodonEncoder, CodonFeatures, KmerEncoder, MutationDetail, PcaTreeError, PropertyEncoder,
+    SavedEncoder, SubstitutionEncoder,
+};
 use ndarray::Array2;
-use std::error::Error;
+use serde::{Deserialize, Serialize};
 
-pub struct OneHotEncoder;
+/// How sequences are turned into the PCA feature matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Encoding {
+    /// 5 columns per position (A, C, G, T, gap); IUPAC codes are split over their bases.
+    OneHot,
+    /// 1 column per position: 0 = consensus, 1 = mismatch (IUPAC codes by the share of
+    /// their bases that differ), -1 = gap.
+    #[default]
+    Relative,
+    /// 23 columns per position: 20 amino acids, gap, stop (`*`) and unknown (`X`).
+    AminoAcid,
+    /// Like `Relative`, but against the amino-acid consensus.
+    AminoAcidRelative,
+    /// Per-residue physicochemical or BLOSUM62 descriptors plus a gap flag.
+    AminoAcidProperties(AaProperties),
+    /// Like `Relative`, but keeping the substituted base, transition / transversion
+    /// or synonymous / non-synonymous class of every mutation.
+    Substitution(MutationDetail),
+    /// Codon-wise features in the reading frame starting at `frame`.
+    Codon { frame: usize, features: CodonFeatures },
+    /// Alignment-free k-mer spectrum; sequences may differ in length.
+    Kmer(KmerEncoder),
+}
 
-impl OneHotEncoder {
-    pub fn new() -> Self {
-        Self
+/// Turns aligned sequences into the `n × p` PCA feature matrix.
+///
+/// Implement this to plug a custom encoding into [`crate::PcaTreeBuilder::encoder`].
+pub trait SequenceEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError>;
+
+    /// The same features as a sparse matrix. The default densely encodes and converts;
+    /// wide encoders override it to never allocate the dense matrix.
+    fn encode_sparse(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        Ok(CsrMatrix::from_dense(&self.encode(sequences)?))
+    }
+
+    /// Names of the `p` feature columns for sequences of length `len`.
+    fn feature_names(&self, len: usize) -> Vec<String>;
+
+    /// The same encoder comparing against `reference` (e.g. a germline) instead of
+    /// its own reference point. `None` if the encoding has no reference point.
+    fn with_reference(&self, _reference: &str) -> Option<Box<dyn SequenceEncoder>> {
+        None
+    }
+
+    /// Number of consecutive feature columns per sequence position, if the features
+    /// are laid out position by position. `None` for position-free encodings.
+    fn channels_per_position(&self) -> Option<usize> {
+        None
+    }
+
+    /// The same encoder with everything it derives from `sequences` (such as the
+    /// consensus) fixed, so that later batches land in the same feature space.
+    /// `None` if the features of a sequence do not depend on the rest of the batch.
+    fn freeze(&self, _sequences: &[String]) -> Result<Option<Box<dyn SequenceEncoder>>, PcaTreeError> {
+        Ok(None)
+    }
+
+    /// Serialisable form of this encoder, for [`PcaTree::save`](crate::PcaTree::save).
+    /// `None` for encoders that cannot be saved, such as custom ones.
+    fn saved(&self) -> Option<SavedEncoder> {
+        None
+    }
+}
+
+impl Encoding {
+    /// The concrete encoder behind this choice.
+    pub fn encoder(&self) -> Box<dyn SequenceEncoder> {
+        match self {
+            Encoding::OneHot => Box::new(OneHotEncoder::new()),
+            Encoding::Relative => Box::new(RelativeEncoder::new(Alphabet::Nucleotide)),
+            Encoding::AminoAcid => Box::new(AminoAcidEncoder::new()),
+            Encoding::AminoAcidRelative => Box::new(RelativeEncoder::new(Alphabet::AminoAcid)),
+            Encoding::AminoAcidProperties(p) => Box::new(PropertyEncoder::new(*p)),
+            Encoding::Substitution(d) => Box::new(SubstitutionEncoder::new(*d)),
+            Encoding::Codon { frame, features } => Box::new(CodonEncoder::new(*frame, *features)),
+            Encoding::Kmer(k) => Box::new(*k),
+        }
+    }
+}
+
+impl SequenceEncoder for Encoding {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        self.encoder().encode(sequences)
+    }
+
+    fn encode_sparse(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        self.encoder().encode_sparse(sequences)
+    }
+
+    fn feature_names(&self, len: usize) -> Vec<String> {
+        self.encoder().feature_names(len)
+    }
+
+    fn with_reference(&self, reference: &str) -> Option<Box<dyn SequenceEncoder>> {
+        self.encoder().with_reference(reference)
+    }
+
+    fn channels_per_position(&self) -> Option<usize> {
+        self.encoder().channels_per_position()
+    }
+
+    fn freeze(&self, sequences: &[String]) -> Result<Option<Box<dyn SequenceEncoder>>, PcaTreeError> {
+        self.encoder().freeze(sequences)
     }
 
-    pub fn encode_batch(&self, sequences: &[String]) -> Result<Array2<f32>, Box<dyn Error>> {
-        if sequences.is_empty() {
-            return Err("No sequences provided".into());
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::Encoding(*self))
+    }
+}
+
+/// Residue alphabet of the input sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Alphabet {
+    #[default]
+    Nucleotide,
+    AminoAcid,
+}
+
+/// Match / mismatch / gap against the per-column consensus or a fixed reference,
+/// one feature per position.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelativeEncoder {
+    pub alphabet: Alphabet,
+    /// Compare against this sequence (e.g. the germline) instead of the consensus.
+    pub reference: Option<String>,
+    /// How the consensus is chosen when there is no reference.
+    pub consensus: ConsensusRules,
+    /// Columns that are always 0, like the dropped gap columns of the consensus
+    /// this encoder was frozen at (see [`SequenceEncoder::freeze`]).
+    pub dropped: Vec<usize>,
+}
+
+impl RelativeEncoder {
+    pub fn new(alphabet: Alphabet) -> Self {
+        Self {
+            alphabet,
+            reference: None,
+            consensus: ConsensusRules::default(),
+            dropped: Vec::new(),
         }
+    }
+
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
 
-        let len = sequences[0].len();
+    pub fn with_consensus(mut self, rules: ConsensusRules) -> Self {
+        self.consensus = rules;
+        self
+    }
+}
+
+impl RelativeEncoder {
+    /// Per-column counts and the consensus this encoder compares against
+    /// when no reference is set.
+    pub fn column_stats(&self, sequences: &[String]) -> Result<ColumnStats, PcaTreeError> {
+        Ok(ColumnStats::new(sequences, self.alphabet)?.with_rules(self.consensus))
+    }
+}
 
-        for (i, s) in sequences.iter().enumerate() {
-            if s.len() != len {
-                return Err(format!(
-                    "Sequence length mismatch at index {}: expected {}, got {}",
-                    i, len, s.len()
-                ).into());
+impl RelativeEncoder {
+    /// The features as a sparse matrix: only mutations and gaps are stored.
+    fn encode_csr(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        let (reference, stats) = match &self.reference {
+            Some(r) => (r.clone(), None),
+            None => {
+                let stats = self.column_stats(sequences)?;
+                (stats.consensus(), Some(stats))
             }
+        };
+
+        let x = match self.alphabet {
+            Alphabet::Nucleotide => OneHotEncoder::new().relative_to_sparse(sequences, &reference)?,
+            Alphabet::AminoAcid => AminoAcidEncoder::new().relative_to_sparse(sequences, &reference)?,
+        };
+        let dropped: Vec<usize> = match &stats {
+            Some(stats) => stats.dropped_columns(),
+            None => self.dropped.clone(),
+        };
+        if dropped.is_empty() {
+            return Ok(x);
         }
+        Ok(x.retain_columns(|c| dropped.binary_search(&c).is_err()))
+    }
+}
+
+impl SequenceEncoder for RelativeEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        Ok(self.encode_csr(sequences)?.to_dense())
+    }
+
+    fn encode_sparse(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        self.encode_csr(sequences)
+    }
+
+    fn feature_names(&self, len: usize) -> Vec<String> {
+        (1..=len).map(|pos| pos.to_string()).collect()
+    }
+
+    fn with_reference(&self, reference: &str) -> Option<Box<dyn SequenceEncoder>> {
+        Some(Box::new(self.clone().with_reference(reference)))
+    }
+
+    fn channels_per_position(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn freeze(&self, sequences: &[String]) -> Result<Option<Box<dyn SequenceEncoder>>, PcaTreeError> {
+        if self.reference.is_some() {
+            return Ok(None);
+        }
+        let stats = self.column_stats(sequences)?;
+        let frozen = RelativeEncoder {
+            dropped: stats.dropped_columns(),
+            ..self.clone().with_reference(stats.consensus())
+        };
+        Ok(Some(Box::new(frozen)))
+    }
+
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::Relative(self.clone()))
+    }
+}
+
+impl SequenceEncoder for OneHotEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        self.encode_batch(sequences)
+    }
+
+    fn encode_sparse(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        sparse_batch(sequences, iupac_weights)
+    }
+
+    fn feature_names(&self, len: usize) -> Vec<String> {
+        channel_names(len, &["A", "C", "G", "T", "gap"])
+    }
+
+    fn channels_per_position(&self) -> Option<usize> {
+        Some(NT_CHANNELS)
+    }
+
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::OneHot)
+    }
+}
+
+impl SequenceEncoder for AminoAcidEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        self.encode_batch(sequences)
+    }
+
+    fn encode_sparse(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        sparse_batch(sequences, aa_weights)
+    }
+
+    fn feature_names(&self, len: usize) -> Vec<String> {
+        let mut channels: Vec<String> = AMINO_ACIDS.iter().map(|&a| (a as char).to_string()).collect();
+        channels.extend(["gap", "stop", "X"].map(String::from));
+        channel_names(len, &channels)
+    }
+
+    fn channels_per_position(&self) -> Option<usize> {
+        Some(AA_CHANNELS)
+    }
+
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::AminoAcid)
+    }
+}
+
+/// Sparse one-hot encoding with `C` channels per position and `weights` per symbol.
+fn sparse_batch<const C: usize>(
+    sequences: &[String],
+    weights: fn(u8) -> Option<[f32; C]>,
+) -> Result<CsrMatrix, PcaTreeError> {
+    let len = aligned_len(sequences)?;
+    let mut x = CsrMatrix::new(C * len);
+
+    for (i, seq) in sequences.iter().enumerate() {
+        let mut row = Vec::new();
+        for (pos, &b) in seq.as_bytes().iter().enumerate() {
+            let Some(w) = weights(b) else {
+                return Err(PcaTreeError::InvalidSymbol {
+                    index: i,
+                    position: pos,
+                    symbol: b as char,
+                });
+            };
+            row.extend(w.iter().enumerate().map(|(c, v)| (C * pos + c, *v)));
+        }
+        x.push_row(row);
+    }
+
+    Ok(x)
+}
+
+/// `<pos>_<channel>` for every position (1-based) and channel.
+pub(crate) fn channel_names<S: AsRef<str>>(len: usize, channels: &[S]) -> Vec<String> {
+    (1..=len)
+        .flat_map(|pos| channels.iter().map(move |c| format!("{}_{}", pos, c.as_ref())))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct OneHotEncoder;
+
+impl OneHotEncoder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// One-hot encode aligned nucleotide sequences into `n × 5L` features.
+    ///
+    /// Each position gets the columns A, C, G, T and gap (`-` or `.`).
+    /// IUPAC ambiguity codes spread their weight evenly over the bases they stand for,
+    /// e.g. `R` = 0.5 A + 0.5 G and `N` = 0.25 on each base.
+    pub fn encode_batch(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        let len = aligned_len(sequences)?;
 
         let n = sequences.len();
-        let d = 4 * len;
+        let d = NT_CHANNELS * len;
 
         let mut x = Array2::<f32>::zeros((n, d));
 
         for (i, seq) in sequences.iter().enumerate() {
-            for (pos, base) in seq.chars().enumerate() {
-                let idx = match base {
-                    'A' | 'a' => 0,
-                    'C' | 'c' => 1,
-                    'G' | 'g' => 2,
-                    'T' | 't' => 3,
-                    _ => return Err(format!("Invalid base {base}").into()),
+            for (pos, &base) in seq.as_bytes().iter().enumerate() {
+                let Some(w) = iupac_weights(base) else {
+                    return Err(PcaTreeError::InvalidSymbol {
+                        index: i,
+                        position: pos,
+                        symbol: base as char,
+                    });
                 };
-                x[[i, 4 * pos + idx]] = 1.0;
+                for (c, v) in w.iter().enumerate() {
+                    x[[i, NT_CHANNELS * pos + c]] = *v;
+                }
             }
         }
 
         Ok(x)
     }
 
-    pub fn encode_relative(&self, sequences: &[String]) -> Result<Array2<f32>, Box<dyn Error>> {
-        if sequences.is_empty() {
-            return Err("No sequences provided".into());
+    /// One column per position against the consensus of the default [`ConsensusRules`]:
+    /// 0 = consensus base, 1 = other base, -1 = gap. An IUPAC code counts with the share of
+    /// its bases the consensus does not stand for, e.g. `N` against `A` as 0.75.
+    /// Use [`RelativeEncoder::with_consensus`] for other consensus rules.
+    pub fn encode_relative(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        // consensus per column (ignore gaps)
+        let consensus = self.column_stats(sequences)?.consensus();
+
+        Ok(self.encode_against(sequences, consensus.as_bytes())?.to_dense())
+    }
+
+    /// Per-column base counts and the consensus `encode_relative` compares against.
+    pub fn column_stats(&self, sequences: &[String]) -> Result<ColumnStats, PcaTreeError> {
+        ColumnStats::new(sequences, Alphabet::Nucleotide)
+    }
+
+    /// Like `encode_relative`, but against a given reference (e.g. the germline)
+    /// instead of the consensus: 0 = same base, 1 = mutated, -1 = gap, IUPAC codes in between.
+    pub fn encode_relative_to(&self, sequences: &[String], reference: &str) -> Result<Array2<f32>, PcaTreeError> {
+        Ok(self.relative_to_sparse(sequences, reference)?.to_dense())
+    }
+
+    /// `encode_relative_to` as a sparse matrix, without the dense intermediate.
+    pub(crate) fn relative_to_sparse(&self, sequences: &[String], reference: &str) -> Result<CsrMatrix, PcaTreeError> {
+        let len = aligned_len(sequences)?;
+        if reference.len() != len {
+            return Err(PcaTreeError::ReferenceLength { expected: len, found: reference.len() });
         }
 
-        let len = sequences[0].len();
+        self.encode_against(sequences, reference.to_ascii_uppercase().as_bytes())
+    }
 
-        for (i, s) in sequences.iter().enumerate() {
-            if s.len() != len {
-                return Err(format!(
-                    "Sequence length mismatch at index {}: expected {}, got {}",
-                    i, len, s.len()
-                ).into());
+    /// Only mismatches (up to 1) and gaps (-1) are stored.
+    fn encode_against(&self, sequences: &[String], reference: &[u8]) -> Result<CsrMatrix, PcaTreeError> {
+        let mut x = CsrMatrix::new(reference.len());
+
+        for (i, seq) in sequences.iter().enumerate() {
+            let mut row = Vec::new();
+            for (j, &b) in seq.as_bytes().iter().enumerate() {
+                let Some(w) = iupac_weights(b) else {
+                    return Err(PcaTreeError::InvalidSymbol { index: i, position: j, symbol: b as char });
+                };
+                let v = if w[4] > 0.0 {
+                    -1.0
+                } else if normalize_base(b) == normalize_base(reference[j]) {
+                    0.0
+                } else {
+                    // weight on bases the reference does not stand for
+                    let r = iupac_weights(reference[j]).unwrap_or_default();
+                    w[..4].iter().zip(&r).filter(|(_, r)| **r == 0.0).map(|(v, _)| v).sum()
+                };
+                row.push((j, v));
             }
+            x.push_row(row);
         }
 
-        let n = sequences.len();
-        let mut x = Array2::<f32>::zeros((n, len));
+        Ok(x)
+    }
 
-        // consensus per column (ignore gaps)
-        let mut consensus = Vec::with_capacity(len);
-
-        for col in 0..len {
-            let mut counts = [0u32; 4];
-
-            for s in sequences {
-                match s.as_bytes()[col] {
-                    b'A' => counts[0] += 1,
-                    b'C' => counts[1] += 1,
-                    b'G' => counts[2] += 1,
-                    b'T' => counts[3] += 1,
-                    _ => {}
+}
+
+/// Encoder for aligned protein sequences (one letter code).
+#[derive(Default)]
+pub struct AminoAcidEncoder;
+
+impl AminoAcidEncoder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// One-hot encode aligned protein sequences into `n × 23L` features.
+    ///
+    /// Columns per position are the 20 amino acids in `ACDEFGHIKLMNPQRSTVWY` order,
+    /// then gap (`-` or `.`), stop (`*`) and unknown (`X`).
+    /// The ambiguity codes B (D/N), Z (E/Q) and J (I/L) are split evenly.
+    pub fn encode_batch(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        let len = aligned_len(sequences)?;
+
+        let mut x = Array2::<f32>::zeros((sequences.len(), AA_CHANNELS * len));
+
+        for (i, seq) in sequences.iter().enumerate() {
+            for (pos, &aa) in seq.as_bytes().iter().enumerate() {
+                let Some(w) = aa_weights(aa) else {
+                    return Err(PcaTreeError::InvalidSymbol {
+                        index: i,
+                        position: pos,
+                        symbol: aa as char,
+                    });
+                };
+                for (c, v) in w.iter().enumerate() {
+                    x[[i, AA_CHANNELS * pos + c]] = *v;
                 }
             }
+        }
+
+        Ok(x)
+    }
 
-            let (idx, _) = counts.iter().enumerate().max_by_key(|(_, c)| *c).unwrap();
-            consensus.push(b"ACGT"[idx]);
+    /// One column per position: 0 = consensus residue, 1 = other residue or stop,
+    /// 0.5 = unknown (`X`), -1 = gap. The consensus only counts the 20 amino acids.
+    pub fn encode_relative(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        let consensus = self.column_stats(sequences)?.consensus();
+
+        let upper: Vec<String> = sequences.iter().map(|s| s.to_ascii_uppercase()).collect();
+        Ok(self.encode_against(&upper, consensus.as_bytes())?.to_dense())
+    }
+
+    /// Per-column residue counts and the consensus `encode_relative` compares against.
+    pub fn column_stats(&self, sequences: &[String]) -> Result<ColumnStats, PcaTreeError> {
+        ColumnStats::new(sequences, Alphabet::AminoAcid)
+    }
+
+    /// Like `encode_relative`, but against a given reference (e.g. the germline).
+    pub fn encode_relative_to(&self, sequences: &[String], reference: &str) -> Result<Array2<f32>, PcaTreeError> {
+        Ok(self.relative_to_sparse(sequences, reference)?.to_dense())
+    }
+
+    /// `encode_relative_to` as a sparse matrix, without the dense intermediate.
+    pub(crate) fn relative_to_sparse(&self, sequences: &[String], reference: &str) -> Result<CsrMatrix, PcaTreeError> {
+        let len = aligned_len(sequences)?;
+        if reference.len() != len {
+            return Err(PcaTreeError::ReferenceLength { expected: len, found: reference.len() });
         }
 
-        // Encode
-        for (i, seq) in sequences.iter().enumerate() {
-            for (j, b) in seq.as_bytes().iter().enumerate() {
-                x[[i, j]] = match *b {
+        let upper: Vec<String> = sequences.iter().map(|s| s.to_ascii_uppercase()).collect();
+        self.encode_against(&upper, reference.to_ascii_uppercase().as_bytes())
+    }
+
+    /// `upper` must already be upper case. Only differences from the reference are stored.
+    fn encode_against(&self, upper: &[String], reference: &[u8]) -> Result<CsrMatrix, PcaTreeError> {
+        let mut x = CsrMatrix::new(reference.len());
+
+        for (i, seq) in upper.iter().enumerate() {
+            let mut row = Vec::new();
+            for (j, &aa) in seq.as_bytes().iter().enumerate() {
+                let v = match aa {
                     b'.' | b'-' => -1.0,
-                    _ => {
-                        if *b == consensus[j] { 0.0 } else { 1.0 }
-                    }
+                    b'X' => 0.5,
+                    _ if aa == reference[j] => 0.0,
+                    _ if aa == b'*' || aa_weights(aa).is_some() => 1.0,
+                    _ => return Err(PcaTreeError::InvalidSymbol {
+                        index: i,
+                        position: j,
+                        symbol: aa as char,
+                    }),
                 };
+                row.push((j, v));
             }
+            x.push_row(row);
         }
 
         Ok(x)
     }
+}
+
+/// The 20 standard amino acids, in feature column order.
+pub(crate) const AMINO_ACIDS: &[u8] = b"ACDEFGHIKLMNPQRSTVWY";
+
+/// Columns per position in `AminoAcidEncoder::encode_batch`: 20 residues, gap, stop, X.
+const AA_CHANNELS: usize = 23;
+
+/// Probability vector over the amino-acid channels for one residue code.
+fn aa_weights(b: u8) -> Option<[f32; AA_CHANNELS]> {
+    let aa = |c: u8| AMINO_ACIDS.iter().position(|&a| a == c);
+
+    let idx: Vec<usize> = match b.to_ascii_uppercase() {
+        b'-' | b'.' => vec![20],
+        b'*' => vec![21],
+        b'X' => vec![22],
+        b'B' => vec![aa(b'D')?, aa(b'N')?],
+        b'Z' => vec![aa(b'E')?, aa(b'Q')?],
+        b'J' => vec![aa(b'I')?, aa(b'L')?],
+        c => vec![aa(c)?],
+    };
+
+    let mut w = [0.0; AA_CHANNELS];
+    for &c in &idx {
+        w[c] = 1.0 / idx.len() as f32;
+    }
+    Some(w)
+}
+
+/// Columns per position in `encode_batch`: A, C, G, T, gap.
+const NT_CHANNELS: usize = 5;
+
+/// A nucleotide the way consensus and reference comparisons see it: upper case,
+/// with RNA `U` read as `T`.
+pub(crate) fn normalize_base(b: u8) -> u8 {
+    match b.to_ascii_uppercase() {
+        b'U' => b'T',
+        b => b,
+    }
+}
 
+/// Probability vector over (A, C, G, T, gap) for one IUPAC nucleotide code.
+pub(crate) fn iupac_weights(b: u8) -> Option<[f32; NT_CHANNELS]> {
+    let bases: &[usize] = match b.to_ascii_uppercase() {
+        b'A' => &[0],
+        b'C' => &[1],
+        b'G' => &[2],
+        b'T' | b'U' => &[3],
+        b'R' => &[0, 2],
+        b'Y' => &[1, 3],
+        b'S' => &[1, 2],
+        b'W' => &[0, 3],
+        b'K' => &[2, 3],
+        b'M' => &[0, 1],
+        b'B' => &[1, 2, 3],
+        b'D' => &[0, 2, 3],
+        b'H' => &[0, 1, 3],
+        b'V' => &[0, 1, 2],
+        b'N' => &[0, 1, 2, 3],
+        b'-' | b'.' => &[4],
+        _ => return None,
+    };
+
+    let mut w = [0.0; NT_CHANNELS];
+    for &c in bases {
+        w[c] = 1.0 / bases.len() as f32;
+    }
+    Some(w)
 }
 
+/// Common length of all sequences, or the first one that differs.
+pub(crate) fn aligned_len(sequences: &[String]) -> Result<usize, PcaTreeError> {
+    let Some(first) = sequences.first() else {
+        return Err(PcaTreeError::EmptyInput);
+    };
+    let len = first.len();
+
+    for (i, s) in sequences.iter().enumerate() {
+        if s.len() != len {
+            return Err(PcaTreeError::LengthMismatch {
+                index: i,
+                expected: len,
+                found: s.len(),
+            });
+        }
+    }
+
+    Ok(len)
+}
@@ -0,0 +1,116 @@
+use ndarray_linalg::error::LinalgError;
+use std::fmt;
+
+/// Everything that can go wrong in the PCA + MST pipeline.
+#[derive(Debug)]
+pub enum PcaTreeError {
+    /// No sequences (or no rows) were given.
+    EmptyInput,
+    /// Sequence `index` does not have the length of the first sequence.
+    LengthMismatch { index: usize, expected: usize, found: usize },
+    /// Sequence `index` has a character the encoder does not understand at `position`.
+    InvalidSymbol { index: usize, position: usize, symbol: char },
+    /// The reference (germline) is not as long as the aligned sequences.
+    ReferenceLength { expected: usize, found: usize },
+    /// The number of ids does not match the number of sequences.
+    IdCountMismatch { ids: usize, seqs: usize },
+    /// The number of row weights does not match the number of rows.
+    WeightCount { weights: usize, rows: usize },
+    /// New data does not have the feature count the model was fitted on.
+    FeatureCount { expected: usize, found: usize },
+    /// The model has to be fitted first.
+    NotFitted,
+    /// k-mer size outside `1..=31`, or above [`MAX_UNHASHED_KMER`](crate::MAX_UNHASHED_KMER)
+    /// without hashing.
+    KmerSize { k: usize, hashed: bool },
+    /// Reading frame offset other than 0, 1 or 2.
+    ReadingFrame(usize),
+    /// Part of the model (e.g. a custom encoder) has no serialisable form.
+    NotSaveable(&'static str),
+    /// NaN or infinite values where finite numbers are required.
+    NonFinite(&'static str),
+    /// Eigen decomposition failed.
+    Linalg(LinalgError),
+    /// Malformed FASTA / FASTQ / AIRR input.
+    InvalidFormat(String),
+    /// A requested AIRR column does not exist.
+    MissingColumn(String),
+    Io(std::io::Error),
+    /// Error from the plotting backend.
+    Plot(String),
+}
+
+impl fmt::Display for PcaTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PcaTreeError::EmptyInput => write!(f, "No sequences provided"),
+            PcaTreeError::LengthMismatch { index, expected, found } => write!(
+                f,
+                "Sequence length mismatch at index {index}: expected {expected}, got {found}"
+            ),
+            PcaTreeError::InvalidSymbol { index, position, symbol } => write!(
+                f,
+                "Invalid base {symbol:?} in sequence {index} at position {position}"
+            ),
+            PcaTreeError::ReferenceLength { expected, found } => write!(
+                f,
+                "Reference length {found} does not match the sequence length {expected}"
+            ),
+            PcaTreeError::IdCountMismatch { ids, seqs } => {
+                write!(f, "Got {ids} ids for {seqs} sequences")
+            }
+            PcaTreeError::WeightCount { weights, rows } => {
+                write!(f, "Got {weights} weights for {rows} rows")
+            }
+            PcaTreeError::FeatureCount { expected, found } => {
+                write!(f, "Got {found} features, the model was fitted on {expected}")
+            }
+            PcaTreeError::NotFitted => write!(f, "The model has not been fitted yet"),
+            PcaTreeError::KmerSize { k, hashed: true } => {
+                write!(f, "k-mer size {k} is not between 1 and 31")
+            }
+            PcaTreeError::KmerSize { k, hashed: false } => write!(
+                f,
+                "k-mer size {k} needs 4^{k} columns; hash the k-mers for k above {}",
+                crate::MAX_UNHASHED_KMER
+            ),
+            PcaTreeError::ReadingFrame(frame) => write!(f, "Reading frame {frame} is not 0, 1 or 2"),
+            PcaTreeError::NotSaveable(what) => write!(f, "Cannot save a model with a {what}"),
+            PcaTreeError::NonFinite(what) => write!(f, "Non-finite values in {what}"),
+            PcaTreeError::Linalg(e) => write!(f, "Eigen decomposition failed: {e}"),
+            PcaTreeError::InvalidFormat(msg) => write!(f, "{msg}"),
+            PcaTreeError::MissingColumn(c) => write!(f, "Column '{c}' not found in AIRR header"),
+            PcaTreeError::Io(e) => write!(f, "{e}"),
+            PcaTreeError::Plot(msg) => write!(f, "Plotting failed: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for PcaTreeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PcaTreeError::Linalg(e) => Some(e),
+            PcaTreeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PcaTreeError {
+    fn from(e: std::io::Error) -> Self {
+        PcaTreeError::Io(e)
+    }
+}
+
+impl From<LinalgError> for PcaTreeError {
+    fn from(e: LinalgError) -> Self {
+        PcaTreeError::Linalg(e)
+    }
+}
+
+#[cfg(feature = "plot")]
+impl<E: std::error::Error + Send + Sync> From<plotters::drawing::DrawingAreaErrorKind<E>> for PcaTreeError {
+    fn from(e: plotters::drawing::DrawingAreaErrorKind<E>) -> Self {
+        PcaTreeError::Plot(e.to_string())
+    }
+}
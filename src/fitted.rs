@@ -0,0 +1,68 @@
+use crate::{Aligner, PcaTreeError, PositionMask, SequenceEncoder};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The encoding of a fitted [`PcaTree`](crate::PcaTree), with everything it learned from the
+/// training sequences fixed: the alignment columns, the column mask and the consensus or
+/// germline the features are relative to.
+///
+/// New sequences encoded with it have the features of the training set, so
+/// [`PcaModel::transform`](crate::PcaModel::transform) puts them into the same PCA space.
+#[derive(Clone)]
+pub struct FittedEncoder {
+    pub(crate) encoder: Arc<dyn SequenceEncoder>,
+    pub(crate) alignment: Option<AlignmentLayout>,
+    positions: Option<Vec<usize>>,
+    features: Vec<String>,
+}
+
+/// Columns of the training alignment that new sequences are aligned into.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AlignmentLayout {
+    pub(crate) aligner: Aligner,
+    /// Consensus residue of every column that is not mostly gaps.
+    pub(crate) columns: Vec<(usize, u8)>,
+    pub(crate) width: usize,
+}
+
+impl FittedEncoder {
+    pub(crate) fn new(
+        encoder: Arc<dyn SequenceEncoder>,
+        alignment: Option<AlignmentLayout>,
+        positions: Option<Vec<usize>>,
+        features: Vec<String>,
+    ) -> Self {
+        Self { encoder, alignment, positions, features }
+    }
+
+    /// Bring new sequences into the training layout: aligned into the training
+    /// alignment's columns (insertions are dropped) and masked.
+    pub fn prepare(&self, sequences: &[String]) -> Vec<String> {
+        let aligned = match &self.alignment {
+            Some(a) => a.aligner.align_into(&a.columns, a.width, sequences),
+            None => sequences.to_vec(),
+        };
+        match &self.positions {
+            Some(p) => aligned.iter().map(|s| PositionMask::apply(s, p)).collect(),
+            None => aligned,
+        }
+    }
+
+    /// Encode new sequences into the features the model was fitted on.
+    pub fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        if sequences.is_empty() {
+            return Err(PcaTreeError::EmptyInput);
+        }
+        let x = self.encoder.encode(&self.prepare(sequences))?;
+        if x.ncols() != self.features.len() {
+            return Err(PcaTreeError::FeatureCount { expected: self.features.len(), found: x.ncols() });
+        }
+        Ok(x)
+    }
+
+    /// Names of the feature columns, as in [`PcaTree::features`](crate::PcaTree::features).
+    pub fn feature_names(&self) -> &[String] {
+        &self.features
+    }
+}
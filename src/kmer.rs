@@ -0,0 +1,176 @@
+use crate::encoder::iupac_weights;
+use crate::{PcaTreeError, SavedEncoder, SequenceEncoder};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+/// Largest k-mer size with one column per k-mer (`4^8` = 65536 columns); larger `k`
+/// needs [`KmerEncoder::hashed`].
+pub const MAX_UNHASHED_KMER: usize = 8;
+
+/// Alignment-free encoding: the k-mer spectrum of every sequence.
+///
+/// Sequences may have any length; gaps are removed before counting and k-mers
+/// containing ambiguous bases are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KmerEncoder {
+    pub k: usize,
+    /// Count a k-mer and its reverse complement as one feature.
+    pub canonical: bool,
+    /// Divide counts by the number of k-mers in the sequence.
+    pub normalized: bool,
+    /// Hash k-mers into this many columns instead of one column per k-mer.
+    pub hash_dim: Option<usize>,
+}
+
+impl KmerEncoder {
+    /// Normalised, non-canonical `k`-mers with one column each (`4^k` columns, so
+    /// [`KmerEncoder::hashed`] is needed for `k` above [`MAX_UNHASHED_KMER`]).
+    /// `k` has to be between 1 and 31.
+    pub fn new(k: usize) -> Result<Self, PcaTreeError> {
+        if k == 0 || k > 31 {
+            return Err(PcaTreeError::KmerSize { k, hashed: true });
+        }
+        Ok(Self {
+            k,
+            canonical: false,
+            normalized: true,
+            hash_dim: None,
+        })
+    }
+
+    /// Check that the k-mer size is usable: between 1 and 31, and at most
+    /// [`MAX_UNHASHED_KMER`] without hashing. Encoding checks this too.
+    pub fn validate(&self) -> Result<(), PcaTreeError> {
+        let hashed = self.hash_dim.is_some();
+        if self.k == 0 || self.k > 31 || (!hashed && self.k > MAX_UNHASHED_KMER) {
+            return Err(PcaTreeError::KmerSize { k: self.k, hashed });
+        }
+        Ok(())
+    }
+
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    pub fn normalized(mut self, normalized: bool) -> Self {
+        self.normalized = normalized;
+        self
+    }
+
+    /// Hash into `dim` columns; keeps large `k` affordable at the cost of collisions.
+    pub fn hashed(mut self, dim: usize) -> Self {
+        self.hash_dim = Some(dim.max(1));
+        self
+    }
+
+    /// Number of feature columns.
+    pub fn dim(&self) -> usize {
+        self.hash_dim.unwrap_or(1 << (2 * self.k))
+    }
+
+    /// Encode into `n × dim()` k-mer counts or frequencies.
+    pub fn encode_batch(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        if sequences.is_empty() {
+            return Err(PcaTreeError::EmptyInput);
+        }
+        self.validate()?;
+
+        let mask = (1u64 << (2 * self.k)) - 1;
+        let mut x = Array2::<f32>::zeros((sequences.len(), self.dim()));
+
+        for (i, seq) in sequences.iter().enumerate() {
+            let mut code = 0u64;
+            let mut valid = 0;
+            let mut total = 0usize;
+
+            for (pos, &b) in seq.as_bytes().iter().enumerate() {
+                let bits = match b.to_ascii_uppercase() {
+                    b'A' => 0,
+                    b'C' => 1,
+                    b'G' => 2,
+                    b'T' | b'U' => 3,
+                    b'-' | b'.' => continue,
+                    c if iupac_weights(c).is_some() => {
+                        valid = 0;
+                        continue;
+                    }
+                    _ => {
+                        return Err(PcaTreeError::InvalidSymbol {
+                            index: i,
+                            position: pos,
+                            symbol: b as char,
+                        });
+                    }
+                };
+
+                code = ((code << 2) | bits) & mask;
+                valid += 1;
+                if valid >= self.k {
+                    x[[i, self.column(code)]] += 1.0;
+                    total += 1;
+                }
+            }
+
+            if self.normalized && total > 0 {
+                x.row_mut(i).mapv_inplace(|v| v / total as f32);
+            }
+        }
+
+        Ok(x)
+    }
+
+    fn column(&self, code: u64) -> usize {
+        let code = if self.canonical { code.min(self.reverse_complement(code)) } else { code };
+        match self.hash_dim {
+            // splitmix64 finaliser: cheap and stable across platforms and runs
+            Some(dim) => {
+                let mut h = code.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                ((h ^ (h >> 31)) % dim as u64) as usize
+            }
+            None => code as usize,
+        }
+    }
+
+    fn reverse_complement(&self, code: u64) -> u64 {
+        let mut out = 0;
+        let mut c = code;
+        for _ in 0..self.k {
+            out = (out << 2) | (3 - (c & 3));
+            c >>= 2;
+        }
+        out
+    }
+
+    fn kmer_name(&self, code: u64) -> String {
+        (0..self.k)
+            .rev()
+            .map(|j| b"ACGT"[((code >> (2 * j)) & 3) as usize] as char)
+            .collect()
+    }
+}
+
+impl SequenceEncoder for KmerEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        self.encode_batch(sequences)
+    }
+
+    /// K-mer strings (`AAC`, ...) or `h<i>` for hashed columns; `len` is not used.
+    /// With `canonical`, columns of non-canonical k-mers stay empty but keep their name.
+    /// Empty for a k-mer size that does not [`validate`](KmerEncoder::validate).
+    fn feature_names(&self, _len: usize) -> Vec<String> {
+        if self.validate().is_err() {
+            return Vec::new();
+        }
+        match self.hash_dim {
+            Some(dim) => (0..dim).map(|i| format!("h{i}")).collect(),
+            None => (0..self.dim() as u64).map(|c| self.kmer_name(c)).collect(),
+        }
+    }
+
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::Kmer(*self))
+    }
+}
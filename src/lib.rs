@@ -1,56 +1,606 @@
 //! PCA-Tree: DNA sequence PCA + MST builder
 
+mod airr;
+mod align;
+mod codon;
+mod consensus;
 mod encoder;
+mod error;
+mod fitted;
+mod kmer;
+mod mask;
 mod pca;
+mod properties;
+mod quality;
+mod reader;
+mod saved;
+mod select;
+mod sparse;
+mod substitution;
+mod svd;
 mod tree;
 
-pub use encoder::OneHotEncoder;
+pub use airr::{parse_airr, read_airr, AirrOptions, SeqGroup};
+pub use align::Aligner;
+pub use codon::{CodonEncoder, CodonFeatures};
+pub use consensus::{ColumnStats, ConsensusRules, GapColumns, TieBreak};
+pub use encoder::{
+    Alphabet, AminoAcidEncoder, Encoding, OneHotEncoder, RelativeEncoder, SequenceEncoder,
+};
+pub use error::PcaTreeError;
+pub use fitted::FittedEncoder;
+pub use kmer::{KmerEncoder, MAX_UNHASHED_KMER};
+pub use mask::{ImgtRegion, PositionMask};
 pub use pca::PcaModel;
-pub use tree::MstTree;
+pub use properties::{AaProperties, PropertyEncoder};
+pub use quality::QualityWeighting;
+pub use reader::{parse_sequences, read_sequences, write_fasta, SeqFormat, SeqRecord};
+pub use saved::{SavedEncoder, MODEL_FORMAT_VERSION};
+pub use select::ComponentSelection;
+pub use sparse::CsrMatrix;
+pub use substitution::{MutationDetail, SubstitutionEncoder};
+pub use svd::{Solver, DEFAULT_SEED};
+pub use tree::{Distance, MstTree};
 
-use ndarray::Array2;
-use std::error::Error;
+use encoder::aligned_len;
+use ndarray::{Array1, Array2};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
 
 /// Combined PCA + MST pipeline structure.
+///
+/// Created unfitted by [`PcaTree::new`] or [`PcaTreeBuilder::build`];
+/// `pca` and `tree` are only filled after [`PcaTree::fit`].
 pub struct PcaTree {
-    //encoder: OneHotEncoder,
     pub pca: PcaModel,
     pub tree: MstTree,
+    /// Sequence names, one per PCA row / tree node.
+    pub ids: Vec<String>,
+    /// Names of the PCA input features, filled by [`PcaTree::fit`].
+    pub features: Vec<String>,
+    /// Gapped sequences actually encoded, if an [`Aligner`] was used.
+    pub alignment: Option<Vec<String>>,
+    /// 0-based alignment columns kept by the [`PositionMask`], filled by [`PcaTree::fit`].
+    pub positions: Option<Vec<usize>>,
+    /// Number of input sequences behind every row / node (all 1 unless collapsed).
+    pub counts: Vec<usize>,
+    /// Ids of the identical sequences merged into every row, if duplicates were collapsed.
+    pub members: Option<Vec<Vec<String>>>,
+    seqs: Vec<String>,
+    /// Phred+33 qualities per sequence, if the input had them.
+    quals: Vec<Option<String>>,
+    encoder: Arc<dyn SequenceEncoder>,
+    aligner: Option<Aligner>,
+    mask: Option<PositionMask>,
+    quality: Option<QualityWeighting>,
+    /// Fit on a sparse matrix, see [`PcaTreeBuilder::sparse`].
+    sparse: bool,
+    distance: Distance,
+    /// Row 0 is the germline added by [`PcaTreeBuilder::germline`].
+    germline: bool,
+    fitted: bool,
+    /// Encoding of the fitted model, for new sequences.
+    fitted_encoder: Option<FittedEncoder>,
 }
 
+/// Id of the germline row added by [`PcaTreeBuilder::germline`].
+pub const GERMLINE_ID: &str = "germline";
+
+/// Configuration for a [`PcaTree`], separate from the data it is fitted on.
+///
+/// ```no_run
+/// use pca_tree::{Distance, Encoding, PcaTree};
+///
+/// let seqs = vec!["ACGTACGT".to_string(), "ACGTTCGT".to_string(), "ACCTTCGT".to_string()];
+/// let model = PcaTree::builder()
+///     .encoding(Encoding::Relative)
+///     .components(2)
+///     .distance(Distance::Euclidean)
+///     .fit(seqs)
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct PcaTreeBuilder {
+    encoder: Arc<dyn SequenceEncoder>,
+    aligner: Option<Aligner>,
+    mask: Option<PositionMask>,
+    quality: Option<QualityWeighting>,
+    collapse: bool,
+    sparse: bool,
+    germline: Option<String>,
+    k: usize,
+    selection: Option<ComponentSelection>,
+    solver: Solver,
+    seed: u64,
+    distance: Distance,
+}
+
+impl Default for PcaTreeBuilder {
+    fn default() -> Self {
+        Self {
+            encoder: Arc::new(Encoding::default()),
+            aligner: None,
+            mask: None,
+            quality: None,
+            collapse: false,
+            sparse: false,
+            germline: None,
+            k: 3,
+            selection: None,
+            solver: Solver::Auto,
+            seed: DEFAULT_SEED,
+            distance: Distance::default(),
+        }
+    }
+}
+
+impl PcaTreeBuilder {
+
+    /// How sequences are turned into numeric features (default: consensus-relative).
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoder = Arc::new(encoding);
+        self
+    }
+
+    /// Use a custom [`SequenceEncoder`] instead of one of the built-in encodings.
+    pub fn encoder<E: SequenceEncoder + 'static>(mut self, encoder: E) -> Self {
+        self.encoder = Arc::new(encoder);
+        self
+    }
+
+    /// Align variable-length sequences before encoding (default: expect aligned input).
+    pub fn align(mut self, aligner: Aligner) -> Self {
+        self.aligner = Some(aligner);
+        self
+    }
+
+    /// Only use the selected alignment columns (after aligning, if enabled) as features.
+    pub fn mask(mut self, mask: PositionMask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Down-weight or mask low-quality bases of FASTQ input (see [`QualityWeighting`]).
+    ///
+    /// Only applies to encodings with per-position features; k-mer and codon encodings
+    /// use the sequences as they are.
+    pub fn quality(mut self, weighting: QualityWeighting) -> Self {
+        self.quality = Some(weighting);
+        self
+    }
+
+    /// Merge identical sequences into one node (named after the first copy) and weight
+    /// it by its abundance in the PCA. The quality string of the first copy is kept.
+    pub fn collapse_duplicates(mut self, collapse: bool) -> Self {
+        self.collapse = collapse;
+        self
+    }
+
+    /// Encode into a sparse matrix and fit the PCA without ever densifying it
+    /// (see [`PcaModel::fit_transform_sparse`]). Worth it for wide one-hot encodings of long
+    /// sequences and the relative / substitution encodings, which only store mutations;
+    /// the coordinates are the same as with the dense path, including the signs of the
+    /// components.
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Analyse the sequences relative to their germline (unmutated ancestor).
+    ///
+    /// The germline becomes row 0 / node 0 with id [`GERMLINE_ID`]: the PCA is centered on it,
+    /// so it sits at the origin, and the tree is rooted at it. Encoders with a reference point
+    /// (see [`SequenceEncoder::with_reference`]) compare to the germline instead of the consensus.
+    pub fn germline(mut self, germline: impl Into<String>) -> Self {
+        self.germline = Some(germline.into());
+        self
+    }
+
+    /// Number of PCA components (default: 3).
+    pub fn components(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Choose the number of components from the data instead of a fixed `k`
+    /// (see [`ComponentSelection`]); `pca.n_components()` tells how many were kept.
+    pub fn select_components(mut self, selection: ComponentSelection) -> Self {
+        self.selection = Some(selection);
+        self
+    }
+
+    /// How the PCA components are computed (default: [`Solver::Auto`]).
+    pub fn solver(mut self, solver: Solver) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Seed of the randomized solver (default: [`DEFAULT_SEED`]).
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Distance used between points in PCA space for the MST (default: Euclidean).
+    pub fn distance(mut self, distance: Distance) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// Create an unfitted model; nodes are named by their 0-based input index.
+    pub fn build(self, seqs: Vec<String>) -> PcaTree {
+        let ids = (0..seqs.len()).map(|i| i.to_string()).collect();
+        let quals = vec![None; seqs.len()];
+        self.build_named(ids, seqs, quals)
+    }
+
+    /// Create an unfitted model from parsed records, keeping their ids and qualities.
+    pub fn build_records(self, records: Vec<SeqRecord>) -> PcaTree {
+        let mut ids = Vec::with_capacity(records.len());
+        let mut seqs = Vec::with_capacity(records.len());
+        let mut quals = Vec::with_capacity(records.len());
+        for r in records {
+            ids.push(r.id);
+            seqs.push(r.seq);
+            quals.push(r.qual);
+        }
+        self.build_named(ids, seqs, quals)
+    }
+
+    fn build_named(
+        self,
+        mut ids: Vec<String>,
+        mut seqs: Vec<String>,
+        mut quals: Vec<Option<String>>,
+    ) -> PcaTree {
+        let mut counts = vec![1; seqs.len()];
+        let mut members = None;
+        if self.collapse {
+            let mut index = HashMap::<String, usize>::new();
+            let mut groups: Vec<Vec<String>> = Vec::new();
+            let (mut u_seqs, mut u_quals) = (Vec::new(), Vec::new());
+
+            for ((id, seq), qual) in ids.into_iter().zip(seqs).zip(quals) {
+                match index.get(&seq) {
+                    Some(&u) => groups[u].push(id),
+                    None => {
+                        index.insert(seq.clone(), groups.len());
+                        groups.push(vec![id]);
+                        u_seqs.push(seq);
+                        u_quals.push(qual);
+                    }
+                }
+            }
+
+            ids = groups.iter().map(|g| g[0].clone()).collect();
+            counts = groups.iter().map(Vec::len).collect();
+            seqs = u_seqs;
+            quals = u_quals;
+            members = Some(groups);
+        }
+
+        let germline = self.germline.is_some();
+        if let Some(g) = self.germline {
+            ids.insert(0, GERMLINE_ID.to_string());
+            seqs.insert(0, g);
+            quals.insert(0, None);
+            counts.insert(0, 1);
+            if let Some(m) = &mut members {
+                m.insert(0, vec![GERMLINE_ID.to_string()]);
+            }
+        }
+
+        PcaTree {
+            pca: {
+                let pca = PcaModel::new(self.k).with_solver(self.solver).with_seed(self.seed);
+                match self.selection {
+                    Some(rule) => pca.with_selection(rule),
+                    None => pca,
+                }
+            },
+            tree: MstTree { edges: Vec::new() },
+            ids,
+            features: Vec::new(),
+            alignment: None,
+            positions: None,
+            counts,
+            members,
+            seqs,
+            quals,
+            encoder: self.encoder,
+            aligner: self.aligner,
+            mask: self.mask,
+            quality: self.quality,
+            sparse: self.sparse,
+            distance: self.distance,
+            germline,
+            fitted: false,
+            fitted_encoder: None,
+        }
+    }
+
+    /// Build and fit in one go.
+    pub fn fit(self, seqs: Vec<String>) -> Result<PcaTree, PcaTreeError> {
+        let mut model = self.build(seqs);
+        model.fit()?;
+        Ok(model)
+    }
+
+    /// Build and fit from parsed FASTA / FASTQ / AIRR records.
+    pub fn fit_records(self, records: Vec<SeqRecord>) -> Result<PcaTree, PcaTreeError> {
+        let mut model = self.build_records(records);
+        model.fit()?;
+        Ok(model)
+    }
+}
 
 
 impl PcaTree {
 
-    /// Build PCA + MST from raw sequences.
-    pub fn new(seqs: Vec<String>, k: usize) -> Result<Self, Box<dyn Error>> {
+    /// Start configuring a model.
+    pub fn builder() -> PcaTreeBuilder {
+        PcaTreeBuilder::default()
+    }
+
+    /// Unfitted model with default settings and `k` components; call [`PcaTree::fit`] next.
+    pub fn new(seqs: Vec<String>, k: usize) -> Self {
+        Self::builder().components(k).build(seqs)
+    }
+
+    /// Unfitted model from parsed records, keeping their ids.
+    pub fn from_records(records: Vec<SeqRecord>, k: usize) -> Self {
+        Self::builder().components(k).build_records(records)
+    }
+
+    /// Encode the sequences, fit the PCA and build the tree in PCA space.
+    pub fn fit(&mut self) -> Result<(), PcaTreeError> {
+
+        if self.ids.len() != self.seqs.len() {
+            return Err(PcaTreeError::IdCountMismatch {
+                ids: self.ids.len(),
+                seqs: self.seqs.len(),
+            });
+        }
+
+        // Optionally bring everything to one length (against the germline if there is one)
+        let aligned = match &self.aligner {
+            Some(a) if self.germline && a.reference.is_none() => {
+                Some(a.clone().with_reference(self.seqs[0].clone()).align(&self.seqs)?)
+            }
+            Some(a) => Some(a.align(&self.seqs)?),
+            None => None,
+        };
+        let input = aligned.as_ref().unwrap_or(&self.seqs);
+
+        // Optionally keep only the selected columns
+        let full_len = match &self.mask {
+            Some(_) => aligned_len(input)?,
+            None => 0,
+        };
+        let positions = self.mask.as_ref().map(|m| m.positions(full_len));
+        let masked: Option<Vec<String>> = positions
+            .as_ref()
+            .map(|p| input.iter().map(|s| PositionMask::apply(s, p)).collect());
+        let input = masked.as_ref().unwrap_or(input);
 
         // Encode sequences numerically
-        let encoder = OneHotEncoder::new();
-        let encoded = encoder.encode_relative(&seqs)?;
+        let germline_encoder = match self.germline {
+            true => self.encoder.with_reference(&input[0]),
+            false => None,
+        };
+        let encoder: &dyn SequenceEncoder = match &germline_encoder {
+            Some(e) => e.as_ref(),
+            None => self.encoder.as_ref(),
+        };
+        // Per-position quality weights of every base
+        let channels = encoder.channels_per_position();
+        let base_weights = match (&self.quality, channels) {
+            (Some(q), Some(_)) => self.base_weights(q, aligned.as_deref(), positions.as_deref())?,
+            _ => vec![None; self.seqs.len()],
+        };
+
+        // Fit PCA, with the germline at the origin and collapsed rows weighted by abundance
+        let weights = self.members.is_some().then(|| {
+            Array1::from_iter(self.counts.iter().map(|&c| c as f32))
+        });
+        if self.sparse {
+            let mut encoded = encoder.encode_sparse(input)?;
+            if let (Some(c), true) = (channels, base_weights.iter().any(Option::is_some)) {
+                encoded = quality::blend_sparse(&encoded, &base_weights, c);
+            }
+            let center = self.germline.then(|| encoded.dense_row(0));
+            let weights = weights.unwrap_or_else(|| Array1::ones(encoded.nrows()));
+            self.pca.fit_transform_sparse_weighted(&encoded, weights.view(), center)?;
+        } else {
+            let mut encoded = encoder.encode(input)?;
+            if let Some(c) = channels {
+                quality::blend_dense(&mut encoded, &base_weights, c);
+            }
+            let center = self.germline.then(|| encoded.row(0).to_owned());
+            match (weights, center) {
+                (Some(w), center) => self.pca.fit_transform_weighted(&encoded, w.view(), center)?,
+                (None, Some(center)) => self.pca.fit_transform_centered(&encoded, center)?,
+                (None, None) => self.pca.fit_transform(&encoded)?,
+            }
+        }
+
+        // the encoder has checked that there is input
+        self.features = match (&positions, channels) {
+            // name the features after the original columns
+            (Some(p), Some(c)) => {
+                let all = encoder.feature_names(full_len);
+                p.iter().flat_map(|&j| all[c * j..c * (j + 1)].iter().cloned()).collect()
+            }
+            _ => encoder.feature_names(input[0].len()),
+        };
+
+        // Keep what the encoding learned from this batch to encode new sequences alike
+        let frozen = encoder.freeze(input)?;
+        let frozen: Arc<dyn SequenceEncoder> = match (frozen, germline_encoder) {
+            (Some(e), _) | (None, Some(e)) => Arc::from(e),
+            (None, None) => self.encoder.clone(),
+        };
+        let layout = match (&self.aligner, &aligned) {
+            (Some(a), Some(msa)) => {
+                let msa: Vec<Vec<u8>> = msa.iter().map(|s| s.as_bytes().to_vec()).collect();
+                Some(fitted::AlignmentLayout {
+                    aligner: a.clone(),
+                    columns: align::consensus_columns(&msa),
+                    width: msa.first().map_or(0, Vec::len),
+                })
+            }
+            _ => None,
+        };
+        self.fitted_encoder = Some(FittedEncoder::new(frozen, layout, positions.clone(), self.features.clone()));
+
+        self.alignment = aligned;
+        self.positions = positions;
+
+        // Build tree in PCA space; Prim starts at node 0, so a germline is the root
+        self.tree = MstTree::build_with(self.pca.coords(), self.distance)?;
+        self.fitted = true;
+
+        Ok(())
+    }
 
-        // Fit PCA
-        let mut pca = PcaModel::new(k);
-        pca.fit_transform(&encoded)?;
+    /// The column selection this model was configured with.
+    pub fn mask(&self) -> Option<&PositionMask> {
+        self.mask.as_ref()
+    }
+
+    /// Column counts, consensus, entropy and gap fraction of the sequences as they were
+    /// encoded: after aligning, before masking, including a germline row.
+    pub fn column_stats(&self, alphabet: Alphabet) -> Result<ColumnStats, PcaTreeError> {
+        ColumnStats::new(self.alignment.as_ref().unwrap_or(&self.seqs), alphabet)
+    }
 
-        // Build tree in PCA space
-        let tree = MstTree::build(pca.coords());
+    /// Quality weight per encoded column of every sequence with qualities.
+    fn base_weights(
+        &self,
+        q: &QualityWeighting,
+        aligned: Option<&[String]>,
+        positions: Option<&[usize]>,
+    ) -> Result<Vec<Option<Vec<f32>>>, PcaTreeError> {
+        let mut out = Vec::with_capacity(self.seqs.len());
+        for (i, qual) in self.quals.iter().enumerate() {
+            let Some(qual) = qual else {
+                out.push(None);
+                continue;
+            };
+            let encoded_seq = aligned.map_or(&self.seqs[i], |a| &a[i]);
+            let mut weights = q.column_weights(encoded_seq, &self.seqs[i], qual)?;
+            if let Some(p) = positions {
+                weights = p.iter().map(|&j| weights.get(j).copied().unwrap_or(1.0)).collect();
+            }
+            out.push(Some(weights));
+        }
+        Ok(out)
+    }
+
+    /// Write `id count members` (comma separated member ids) per row.
+    pub fn members_to_tsv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
 
-        Ok(Self {
-            //encoder,
-            pca,
-            tree,
-        })
+        writeln!(w, "id\tcount\tmembers")?;
+        for (i, (id, count)) in self.ids.iter().zip(&self.counts).enumerate() {
+            let members = match &self.members {
+                Some(m) => m.get(i).map_or(String::new(), |m| m.join(",")),
+                None => id.clone(),
+            };
+            writeln!(w, "{}\t{}\t{}", id, count, members)?;
+        }
+        Ok(())
     }
 
-    /// PCA coordinates accessor
-    pub fn coords(&self) -> &Array2<f32> {
-        self.pca.coords()
+    /// Encoder for new sequences in the feature space of this model, `None` before fitting.
+    pub fn fitted_encoder(&self) -> Option<&FittedEncoder> {
+        self.fitted_encoder.as_ref()
     }
 
-    /// Tree edge list
-    pub fn tree(&self) -> &Vec<(usize, usize, f32)> {
+    /// Encode new sequences like the fitted ones and project them into the fitted PCA
+    /// space. Qualities, duplicates and the germline of the new batch play no role.
+    pub fn transform(&self, seqs: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        let encoder = self.fitted_encoder.as_ref().ok_or(PcaTreeError::NotFitted)?;
+        self.pca.transform(&encoder.encode(seqs)?)
+    }
+
+    /// Closest fitted node and its distance for every row of `coords`
+    /// (e.g. from [`PcaTree::transform`]), measured like the tree edges.
+    pub fn nearest_nodes(&self, coords: &Array2<f32>) -> Result<Vec<(usize, f32)>, PcaTreeError> {
+        if !self.fitted {
+            return Err(PcaTreeError::NotFitted);
+        }
+        let fitted = self.pca.coords();
+        if coords.ncols() != fitted.ncols() {
+            return Err(PcaTreeError::FeatureCount { expected: fitted.ncols(), found: coords.ncols() });
+        }
+        Ok(coords
+            .outer_iter()
+            .map(|row| {
+                fitted
+                    .outer_iter()
+                    .map(|node| self.distance.between(row, node))
+                    .enumerate()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .unwrap_or((0, f32::INFINITY))
+            })
+            .collect())
+    }
+
+    /// Write `id PC1 .. PCk nearest distance` for projected sequences, with the id
+    /// of the closest fitted node.
+    pub fn projection_to_tsv<P: AsRef<Path>>(
+        &self,
+        path: P,
+        ids: &[String],
+        coords: &Array2<f32>,
+    ) -> Result<(), PcaTreeError> {
+        if ids.len() != coords.nrows() {
+            return Err(PcaTreeError::IdCountMismatch { ids: ids.len(), seqs: coords.nrows() });
+        }
+        let nearest = self.nearest_nodes(coords)?;
+        let mut w = BufWriter::new(File::create(path)?);
+
+        write!(w, "id")?;
+        for j in 0..coords.ncols() {
+            write!(w, "\tPC{}", j + 1)?;
+        }
+        writeln!(w, "\tnearest\tdistance")?;
+
+        for ((id, row), (node, d)) in ids.iter().zip(coords.outer_iter()).zip(nearest) {
+            write!(w, "{}", id)?;
+            for v in row {
+                write!(w, "\t{:.6}", v)?;
+            }
+            writeln!(w, "\t{}\t{:.6}", self.ids[node], d)?;
+        }
+        Ok(())
+    }
+
+    /// Whether row 0 / node 0 is the germline.
+    pub fn has_germline(&self) -> bool {
+        self.germline
+    }
+
+    pub fn is_fitted(&self) -> bool {
+        self.fitted
+    }
+
+    /// PCA coordinates, `None` before fitting.
+    pub fn coords(&self) -> Option<&Array2<f32>> {
+        self.fitted.then(|| self.pca.coords())
+    }
+
+    /// Tree edge list, empty before fitting.
+    pub fn tree(&self) -> &[(usize, usize, f32)] {
         &self.tree.edges
+    }
 
+    /// Sequence names in row order
+    pub fn ids(&self) -> &[String] {
+        &self.ids
     }
-}
\ No newline at end of file
+}
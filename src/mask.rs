@@ -0,0 +1,136 @@
+use crate::{Alphabet, PcaTreeError};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Framework and complementarity determining regions of an IMGT-gapped V sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImgtRegion {
+    Fr1,
+    Cdr1,
+    Fr2,
+    Cdr2,
+    Fr3,
+    /// From IMGT position 105 to the end of the sequence: gapped V alignments
+    /// do not fix the CDR3 length, so this includes FR4 if present.
+    Cdr3,
+}
+
+impl ImgtRegion {
+    /// 1-based inclusive IMGT codon positions; `None` as end means "to the end".
+    fn codons(&self) -> (usize, Option<usize>) {
+        match self {
+            ImgtRegion::Fr1 => (1, Some(26)),
+            ImgtRegion::Cdr1 => (27, Some(38)),
+            ImgtRegion::Fr2 => (39, Some(55)),
+            ImgtRegion::Cdr2 => (56, Some(65)),
+            ImgtRegion::Fr3 => (66, Some(104)),
+            ImgtRegion::Cdr3 => (105, None),
+        }
+    }
+
+    /// 0-based half-open column range in a gapped sequence of the given alphabet.
+    fn columns(&self, alphabet: Alphabet) -> Range<usize> {
+        let unit = match alphabet {
+            Alphabet::Nucleotide => 3,
+            Alphabet::AminoAcid => 1,
+        };
+        let (start, end) = self.codons();
+        unit * (start - 1)..end.map_or(usize::MAX, |e| unit * e)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "fr1" | "fwr1" => Some(ImgtRegion::Fr1),
+            "cdr1" => Some(ImgtRegion::Cdr1),
+            "fr2" | "fwr2" => Some(ImgtRegion::Fr2),
+            "cdr2" => Some(ImgtRegion::Cdr2),
+            "fr3" | "fwr3" => Some(ImgtRegion::Fr3),
+            "cdr3" => Some(ImgtRegion::Cdr3),
+            _ => None,
+        }
+    }
+}
+
+/// Selects the alignment columns that become PCA features.
+///
+/// Built from 1-based inclusive position ranges, a boolean mask or IMGT region names,
+/// and optionally inverted to drop e.g. primer regions instead of keeping them.
+/// Columns beyond the sequence length are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionMask {
+    /// 0-based half-open column ranges.
+    ranges: Vec<Range<usize>>,
+    /// Keep everything except `ranges`.
+    exclude: bool,
+}
+
+impl PositionMask {
+    /// Keep the 1-based inclusive `(start, end)` positions.
+    pub fn regions(regions: &[(usize, usize)]) -> Self {
+        Self {
+            ranges: regions.iter().map(|&(s, e)| s.saturating_sub(1)..e).collect(),
+            exclude: false,
+        }
+    }
+
+    /// Keep the columns that are `true`.
+    pub fn from_bools(keep: &[bool]) -> Self {
+        let ranges = keep
+            .iter()
+            .enumerate()
+            .filter(|(_, k)| **k)
+            .map(|(i, _)| i..i + 1)
+            .collect();
+        Self { ranges, exclude: false }
+    }
+
+    /// Keep the given IMGT regions of IMGT-gapped nucleotide or protein sequences.
+    pub fn imgt(regions: &[ImgtRegion], alphabet: Alphabet) -> Self {
+        Self {
+            ranges: regions.iter().map(|r| r.columns(alphabet)).collect(),
+            exclude: false,
+        }
+    }
+
+    /// Parse a comma separated list of `start-end` ranges, single positions
+    /// and IMGT region names, e.g. `cdr1,cdr2,300-320`.
+    pub fn parse(spec: &str, alphabet: Alphabet) -> Result<Self, PcaTreeError> {
+        let invalid = |part: &str| PcaTreeError::InvalidFormat(format!("Invalid mask region '{part}'"));
+        let mut ranges = Vec::new();
+
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            if let Some(region) = ImgtRegion::from_name(part) {
+                ranges.push(region.columns(alphabet));
+                continue;
+            }
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let start: usize = start.trim().parse().map_err(|_| invalid(part))?;
+            let end: usize = end.trim().parse().map_err(|_| invalid(part))?;
+            if start == 0 || end < start {
+                return Err(invalid(part));
+            }
+            ranges.push(start - 1..end);
+        }
+
+        Ok(Self { ranges, exclude: false })
+    }
+
+    /// Drop the selected columns and keep everything else.
+    pub fn invert(mut self) -> Self {
+        self.exclude = !self.exclude;
+        self
+    }
+
+    /// Kept 0-based columns of a sequence of length `len`, in order.
+    pub fn positions(&self, len: usize) -> Vec<usize> {
+        (0..len)
+            .filter(|i| self.ranges.iter().any(|r| r.contains(i)) != self.exclude)
+            .collect()
+    }
+
+    /// The characters of `seq` at `positions`.
+    pub fn apply(seq: &str, positions: &[usize]) -> String {
+        let bytes = seq.as_bytes();
+        positions.iter().filter_map(|&p| bytes.get(p).map(|&b| b as char)).collect()
+    }
+}
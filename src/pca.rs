@@ -1,9 +1,13 @@
-use ndarray::{Array1, Array2, Axis};
+use crate::select::{permute_columns, permute_csr_columns, SplitMix64};
+use crate::svd::randomized_svd;
+use crate::{ComponentSelection, CsrMatrix, PcaTreeError, Solver, DEFAULT_SEED};
+use ndarray::{Array1, Array2, ArrayView1, Axis};
 use ndarray_linalg::eigh::Eigh;
-use std::error::Error;
 use ndarray_linalg::UPLO;
+use serde::{Deserialize, Serialize};
 #[cfg(feature = "plot")]
 use plotters::prelude::*;
+#[cfg(feature = "plot")]
 use std::collections::HashMap;
 use std::io::{BufWriter};
 use std::fs::{File};
@@ -11,11 +15,37 @@ use std::io::Write;
 use std::path::Path;
 
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PcaModel {
     pub k: usize,
     pub mean: Array1<f32>,
     pub components: Array2<f32>,
     pub coords: Array2<f32>,
+    /// Covariance eigenvalues (variance per component), largest first: all of them with
+    /// the full solver, only the fitted ones with the randomized solver.
+    pub eigenvalues: Array1<f32>,
+    /// Rule that picks the number of components at fit time instead of `k`.
+    pub selection: Option<ComponentSelection>,
+    /// How the components are computed.
+    pub solver: Solver,
+    /// Seed of the randomized solver.
+    pub seed: u64,
+    /// Total variance of the data, also known when not every eigenvalue is.
+    trace: f32,
+}
+
+/// Flip every component so that its largest loading is positive: eigenvectors are only
+/// defined up to sign, and the solvers differ in it. Loadings within rounding error of the
+/// largest (common with one-hot features) count as equal, and the first of them decides.
+fn orient(mut comps: Array2<f32>) -> Array2<f32> {
+    for mut col in comps.columns_mut() {
+        let max = col.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+        let first = col.iter().find(|v| v.abs() >= max * (1.0 - 1e-3));
+        if first.is_some_and(|v| *v < 0.0) {
+            col.mapv_inplace(|v| -v);
+        }
+    }
+    comps
 }
 
 impl PcaModel {
@@ -26,9 +56,34 @@ impl PcaModel {
             mean: Array1::zeros(0),
             components: Array2::zeros((0, 0)),
             coords: Array2::zeros((0, 0)),
+            eigenvalues: Array1::zeros(0),
+            selection: None,
+            solver: Solver::Auto,
+            seed: DEFAULT_SEED,
+            trace: 0.0,
         }
     }
 
+    /// Choose the number of components from the eigenvalues when fitting;
+    /// [`PcaModel::n_components`] tells how many were kept.
+    pub fn with_selection(mut self, selection: ComponentSelection) -> Self {
+        self.selection = Some(selection);
+        self
+    }
+
+    /// Solver for the components. A [`ComponentSelection`] needs every eigenvalue and
+    /// therefore always uses the full solver.
+    pub fn with_solver(mut self, solver: Solver) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Seed of the randomized solver; the same seed gives the same components.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
     /// Write PCA coordinates to TSV (n rows × k columns).
     pub fn to_tsv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         self.to_delimited( path, '\t' )
@@ -52,33 +107,379 @@ impl PcaModel {
         }
         Ok(())
     }
-    
 
-    pub fn fit_transform(&mut self, x: &Array2<f32>) -> Result<(), Box<dyn Error>> {
+    /// Write PCA coordinates to TSV with a header line and the sequence id as first column.
+    pub fn to_tsv_named<P: AsRef<Path>>(&self, path: P, ids: &[String]) -> std::io::Result<()> {
+        self.to_delimited_named(path, '\t', ids)
+    }
+
+    /// Like `to_delimited`, but with a `id PC1 .. PCk` header and one id per row.
+    pub fn to_delimited_named<P: AsRef<Path>>(&self, path: P, sep: char, ids: &[String]) -> std::io::Result<()> {
+        self.write_named(path, sep, ids, None)
+    }
+
+    /// Like `to_tsv_named`, with the abundance of every (collapsed) row as second column.
+    pub fn to_tsv_counted<P: AsRef<Path>>(&self, path: P, ids: &[String], counts: &[usize]) -> std::io::Result<()> {
+        self.write_named(path, '\t', ids, Some(counts))
+    }
+
+    fn write_named<P: AsRef<Path>>(
+        &self,
+        path: P,
+        sep: char,
+        ids: &[String],
+        counts: Option<&[usize]>,
+    ) -> std::io::Result<()> {
+        let rows = self.coords.nrows();
+        if ids.len() != rows || counts.is_some_and(|c| c.len() != rows) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} ids for {} PCA rows", ids.len(), rows),
+            ));
+        }
+
+        let f = File::create(path)?;
+        let mut w = BufWriter::new(f);
+
+        write!(w, "id")?;
+        if counts.is_some() {
+            write!(w, "{}count", sep)?;
+        }
+        for j in 0..self.coords.ncols() {
+            write!(w, "{}PC{}", sep, j + 1)?;
+        }
+        writeln!(w)?;
+
+        for (i, (id, row)) in ids.iter().zip(self.coords.outer_iter()).enumerate() {
+            write!(w, "{}", id)?;
+            if let Some(c) = counts {
+                write!(w, "{}{}", sep, c[i])?;
+            }
+            for v in row {
+                write!(w, "{}{:.6}", sep, v)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+
+    /// Fit the PCA on `x` (n rows × p features) and store the projected coordinates.
+    ///
+    /// At most `p` components are kept, so `k` larger than the feature count is clamped.
+    /// A single row or identical rows give a zero covariance and therefore all-zero coordinates.
+    pub fn fit_transform(&mut self, x: &Array2<f32>) -> Result<(), PcaTreeError> {
+        let Some(mean) = x.mean_axis(Axis(0)) else {
+            return Err(PcaTreeError::EmptyInput);
+        };
+        self.fit_transform_centered(x, mean)
+    }
+
+    /// Like `fit_transform`, but with `center` as the origin instead of the column means,
+    /// e.g. the encoded germline so that the unmutated ancestor sits at 0.
+    pub fn fit_transform_centered(&mut self, x: &Array2<f32>, center: Array1<f32>) -> Result<(), PcaTreeError> {
+        self.fit_inner(x, center, None)
+    }
+
+    /// Weighted PCA: row `i` counts `weights[i]` times, e.g. the abundance of a collapsed
+    /// duplicate. Centered on the weighted mean, or on `center` if given.
+    pub fn fit_transform_weighted(
+        &mut self,
+        x: &Array2<f32>,
+        weights: ArrayView1<f32>,
+        center: Option<Array1<f32>>,
+    ) -> Result<(), PcaTreeError> {
+        if weights.len() != x.nrows() {
+            return Err(PcaTreeError::WeightCount { weights: weights.len(), rows: x.nrows() });
+        }
+        if !weights.iter().all(|w| w.is_finite() && *w >= 0.0) {
+            return Err(PcaTreeError::NonFinite("weights (or negative)"));
+        }
+
+        let total = weights.sum();
+        let center = match center {
+            Some(c) => c,
+            None if total > 0.0 => weights.dot(x) / total,
+            None => return Err(PcaTreeError::EmptyInput),
+        };
+        self.fit_inner(x, center, Some(weights))
+    }
+
+    fn fit_inner(
+        &mut self,
+        x: &Array2<f32>,
+        center: Array1<f32>,
+        weights: Option<ArrayView1<f32>>,
+    ) -> Result<(), PcaTreeError> {
         let (n, p) = x.dim();
+        if n == 0 {
+            return Err(PcaTreeError::EmptyInput);
+        }
+        if center.len() != p {
+            return Err(PcaTreeError::ReferenceLength { expected: p, found: center.len() });
+        }
 
-        let mean = x.mean_axis(Axis(0)).unwrap();
+        let mean = center;
         let mut centered = x.clone();
 
         for mut row in centered.outer_iter_mut() {
             row -= &mean;
         }
 
-        let cov = centered.t().dot(&centered) / (n as f32 - 1.0);
-        let (eigvals, eigvecs) = cov.eigh(UPLO::Upper)?;
-
-        let mut idx: Vec<_> = (0..eigvals.len()).collect();
-        idx.sort_by(|a, b| eigvals[*b].partial_cmp(&eigvals[*a]).unwrap());
-
-        let comps = Array2::from_shape_fn((p, self.k), |(i, j)| eigvecs[(i, idx[j])]);
+        let rows = weights.map_or(n as f32, |w| w.sum());
+        let (comps, eigenvalues, trace) = if p == 0 {
+            (Array2::zeros((0, 0)), Array1::zeros(0), 0.0)
+        } else if self.randomized(n, p) {
+            // A = sqrt(w) (X - 1 cᵀ) / sqrt(W - 1), so that AᵀA is the covariance
+            let scale = match weights {
+                Some(w) => w.mapv(f32::sqrt),
+                None => Array1::ones(n),
+            } / (rows.max(2.0) - 1.0).sqrt();
+            let a = &centered * &scale.insert_axis(Axis(1));
+            let trace = a.iter().map(|v| v * v).sum();
+            let (comps, eigenvalues) =
+                randomized_svd(n, p, self.k, self.seed, |m| a.dot(m), |y| a.t().dot(y))?;
+            (comps, eigenvalues, trace)
+        } else {
+            let (comps, eigenvalues) = self.top_components(covariance(&centered, weights), rows, p, |rng| {
+                covariance(&permute_columns(&centered, rng), weights)
+            })?;
+            let trace = eigenvalues.sum();
+            (comps, eigenvalues, trace)
+        };
+        let comps = orient(comps);
         let proj = centered.dot(&comps);
 
         self.mean = mean;
         self.components = comps;
         self.coords = proj;
+        self.eigenvalues = eigenvalues;
+        self.trace = trace;
 
         Ok(())
     }
+
+    /// Like `fit_transform`, but on a sparse matrix that is never densified or centered:
+    /// the column means are subtracted implicitly. The full solver decomposes the `p × p`
+    /// covariance or, with fewer rows than features, the `n × n` Gram matrix of the rows,
+    /// so memory is `nnz + min(n, p)²` instead of `n × p`. The randomized solver needs
+    /// `nnz + (n + p) k`.
+    pub fn fit_transform_sparse(&mut self, x: &CsrMatrix) -> Result<(), PcaTreeError> {
+        let weights = Array1::ones(x.nrows());
+        self.fit_transform_sparse_weighted(x, weights.view(), None)
+    }
+
+    /// Sparse counterpart of [`PcaModel::fit_transform_weighted`].
+    pub fn fit_transform_sparse_weighted(
+        &mut self,
+        x: &CsrMatrix,
+        weights: ArrayView1<f32>,
+        center: Option<Array1<f32>>,
+    ) -> Result<(), PcaTreeError> {
+        let (n, p) = (x.nrows(), x.ncols());
+        if n == 0 {
+            return Err(PcaTreeError::EmptyInput);
+        }
+        if weights.len() != n {
+            return Err(PcaTreeError::WeightCount { weights: weights.len(), rows: n });
+        }
+        if !weights.iter().all(|w| w.is_finite() && *w >= 0.0) {
+            return Err(PcaTreeError::NonFinite("weights (or negative)"));
+        }
+
+        let total = weights.sum();
+        let (sum, sumsq) = x.weighted_column_sums(weights);
+        let center = match center {
+            Some(c) if c.len() != p => {
+                return Err(PcaTreeError::ReferenceLength { expected: p, found: c.len() });
+            }
+            Some(c) => c,
+            None if total > 0.0 => &sum / total,
+            None => return Err(PcaTreeError::EmptyInput),
+        };
+
+        // A = diag(sqrt(w / (W - 1))) (X - 1 cᵀ), so that AᵀA is the covariance;
+        // it is only applied, never formed
+        let denom = total.max(2.0) - 1.0;
+        let scale = weights.mapv(|w| (w / denom).sqrt()).insert_axis(Axis(1));
+        let mul = |m: &Array2<f32>| {
+            let shift = center.dot(m);
+            let mut y = x.dot(m);
+            for mut row in y.outer_iter_mut() {
+                row -= &shift;
+            }
+            y * &scale
+        };
+        // Aᵀ Y = Xᵀ (s ⊙ Y) - c 1ᵀ (s ⊙ Y)
+        let tmul = |y: &Array2<f32>| {
+            let scaled = y * &scale;
+            let colsum = scaled.sum_axis(Axis(0)).insert_axis(Axis(0));
+            x.t_dot(&scaled) - center.view().insert_axis(Axis(1)).dot(&colsum)
+        };
+
+        let (comps, eigenvalues, trace) = if p == 0 {
+            (Array2::zeros((0, 0)), Array1::zeros(0), 0.0)
+        } else if self.randomized(n, p) {
+            // Σ_j Σ_i w_i (x_ij - c_j)² = Σ_j (q_j - 2 c_j s_j + W c_j²)
+            let trace = (&sumsq - &(&center * &sum * 2.0) + &(&center * &center * total)).sum() / denom;
+            let (comps, eigenvalues) = randomized_svd(n, p, self.k, self.seed, mul, tmul)?;
+            (comps, eigenvalues, trace)
+        } else if n < p {
+            // AAᵀ has the non-zero eigenvalues of AᵀA; its eigenvectors U give V = Aᵀ U Σ⁻¹
+            let scale = scale.column(0);
+            let gram = |x: &CsrMatrix| centered_row_gram(x, &center, scale);
+            let (u, eigenvalues) =
+                self.top_components(gram(x), total, p, |rng| gram(&permute_csr_columns(x, rng)))?;
+            let mut comps = tmul(&u);
+            for (mut col, v) in comps.axis_iter_mut(Axis(1)).zip(eigenvalues.iter()) {
+                if *v > 0.0 {
+                    col /= v.sqrt();
+                } else {
+                    col.fill(0.0);
+                }
+            }
+            let trace = eigenvalues.sum();
+            (comps, eigenvalues, trace)
+        } else {
+            let (_, gram) = x.weighted_moments(weights);
+            let cov = sparse_covariance(&sum, &gram, &center, total);
+            let (comps, eigenvalues) = self.top_components(cov, total, p, |rng| {
+                let (sum, gram) = permute_csr_columns(x, rng).weighted_moments(weights);
+                sparse_covariance(&sum, &gram, &center, total)
+            })?;
+            let trace = eigenvalues.sum();
+            (comps, eigenvalues, trace)
+        };
+
+        // (X - 1 cᵀ) V = X V - 1 (cᵀ V)
+        let comps = orient(comps);
+        let shift = center.dot(&comps);
+        let mut proj = x.dot(&comps);
+        for mut row in proj.outer_iter_mut() {
+            row -= &shift;
+        }
+
+        self.mean = center;
+        self.components = comps;
+        self.coords = proj;
+        self.eigenvalues = eigenvalues;
+        self.trace = trace;
+
+        Ok(())
+    }
+
+    /// Whether to fit `n × p` data with the randomized solver.
+    fn randomized(&self, n: usize, p: usize) -> bool {
+        self.selection.is_none() && self.solver.randomized(n, p, self.k)
+    }
+
+    /// Eigenvectors of the `k` largest eigenvalues of a covariance (or row Gram) matrix
+    /// of `rows` rows and `features` columns, as columns, and all `features` eigenvalues
+    /// (the ones a Gram matrix lacks are 0). With a [`ComponentSelection`] the rule picks
+    /// `k`; `null` gives the same matrix of a column-permuted copy of the data for
+    /// parallel analysis.
+    fn top_components<F>(
+        &self,
+        cov: Array2<f32>,
+        rows: f32,
+        features: usize,
+        mut null: F,
+    ) -> Result<(Array2<f32>, Array1<f32>), PcaTreeError>
+    where
+        F: FnMut(&mut SplitMix64) -> Array2<f32>,
+    {
+        let pad = |v: Array1<f32>| {
+            let mut all = Array1::zeros(features.max(v.len()));
+            all.slice_mut(ndarray::s![..v.len()]).assign(&v);
+            all
+        };
+        let (eigvals, eigvecs) = sorted_eigh(cov)?;
+        let eigvals = pad(eigvals);
+        let k = match self.selection {
+            Some(rule) => rule.choose(&eigvals, rows, |rng| Ok(pad(sorted_eigh(null(rng))?.0)))?,
+            None => self.k,
+        };
+        let k = k.min(eigvecs.ncols());
+        Ok((eigvecs.slice(ndarray::s![.., ..k]).to_owned(), eigvals))
+    }
+
+    /// Project new rows onto the fitted components, centered on the fitted mean.
+    /// `x` needs the columns the model was fitted on.
+    pub fn transform(&self, x: &Array2<f32>) -> Result<Array2<f32>, PcaTreeError> {
+        if x.ncols() != self.mean.len() {
+            return Err(PcaTreeError::FeatureCount { expected: self.mean.len(), found: x.ncols() });
+        }
+        let mut centered = x.clone();
+        for mut row in centered.outer_iter_mut() {
+            row -= &self.mean;
+        }
+        Ok(centered.dot(&self.components))
+    }
+
+    /// Sparse counterpart of [`PcaModel::transform`]; `x` is never densified.
+    pub fn transform_sparse(&self, x: &CsrMatrix) -> Result<Array2<f32>, PcaTreeError> {
+        if x.ncols() != self.mean.len() {
+            return Err(PcaTreeError::FeatureCount { expected: self.mean.len(), found: x.ncols() });
+        }
+        let shift = self.mean.dot(&self.components);
+        let mut proj = x.dot(&self.components);
+        for mut row in proj.outer_iter_mut() {
+            row -= &shift;
+        }
+        Ok(proj)
+    }
+
+    /// Number of components actually fitted (`k` clamped to the feature count).
+    pub fn n_components(&self) -> usize {
+        self.components.ncols()
+    }
+
+    /// Variance captured by each fitted component.
+    pub fn explained_variance(&self) -> ArrayView1<'_, f32> {
+        self.eigenvalues.slice(ndarray::s![..self.n_components()])
+    }
+
+    /// Share of the total variance captured by each fitted component.
+    pub fn explained_variance_ratio(&self) -> Array1<f32> {
+        let total = self.total_variance();
+        if total > 0.0 {
+            self.explained_variance().mapv(|v| v / total)
+        } else {
+            Array1::zeros(self.n_components())
+        }
+    }
+
+    /// Running sum of `explained_variance_ratio`.
+    pub fn cumulative_variance(&self) -> Array1<f32> {
+        let mut acc = 0.0;
+        self.explained_variance_ratio().mapv(|r| {
+            acc += r;
+            acc
+        })
+    }
+
+    /// Sum of all eigenvalues, i.e. the total variance of the encoded features.
+    pub fn total_variance(&self) -> f32 {
+        // models assembled by hand from the public fields have no trace
+        self.trace.max(self.eigenvalues.sum())
+    }
+
+    /// Write `PC eigenvalue ratio cumulative` for every known eigenvalue (with the full solver
+    /// not only the fitted ones), the numbers behind a scree plot.
+    pub fn variance_to_tsv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        let total = self.total_variance();
+
+        writeln!(w, "PC\teigenvalue\tratio\tcumulative")?;
+        let mut acc = 0.0;
+        for (j, v) in self.eigenvalues.iter().enumerate() {
+            let ratio = if total > 0.0 { v / total } else { 0.0 };
+            acc += ratio;
+            writeln!(w, "PC{}\t{:.6}\t{:.6}\t{:.6}", j + 1, v, ratio, acc)?;
+        }
+        Ok(())
+    }
+
     pub fn coords(&self) -> &Array2<f32> {
         &self.coords
     }
@@ -91,12 +492,15 @@ impl PcaModel {
         &self,
         tree: &crate::MstTree,
         outfile: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), PcaTreeError> {
 
         println!("Plotting PCA with cluster coloring → {}", outfile);
 
         let coords = &self.coords;
         let n = coords.nrows();
+        if coords.ncols() < 2 {
+            return Err(PcaTreeError::Plot("need at least 2 PCA components".to_string()));
+        }
 
         // --- run elbow clustering ---
         let clusters = tree.clusters_elbow(n);
@@ -154,10 +558,61 @@ impl PcaModel {
         root.present()?;
         Ok(())
     }
+    /// Scree plot: explained variance ratio (bars) and cumulative ratio (line)
+    /// of the first 30 eigenvalues at most.
+    #[cfg(feature = "plot")]
+    pub fn plot_scree(&self, outfile: &str) -> Result<(), PcaTreeError> {
+        let total = self.total_variance();
+        if total <= 0.0 {
+            return Err(PcaTreeError::Plot("no variance to plot".to_string()));
+        }
+        let ratios: Vec<f32> = self.eigenvalues.iter().take(30).map(|v| v / total).collect();
+
+        let root = BitMapBackend::new(outfile, (900, 600)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption("Scree plot", ("sans-serif", 30))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(50)
+            .build_cartesian_2d(0.5f32..ratios.len() as f32 + 0.5, 0f32..1f32)?;
+
+        chart
+            .configure_mesh()
+            .x_desc("component")
+            .y_desc("variance ratio")
+            .draw()?;
+
+        chart.draw_series(ratios.iter().enumerate().map(|(j, r)| {
+            let x = j as f32 + 1.0;
+            Rectangle::new([(x - 0.35, 0.0), (x + 0.35, *r)], BLUE.filled())
+        }))?;
+
+        let mut acc = 0.0;
+        let cumulative: Vec<(f32, f32)> = ratios
+            .iter()
+            .enumerate()
+            .map(|(j, r)| {
+                acc += r;
+                (j as f32 + 1.0, acc)
+            })
+            .collect();
+        chart.draw_series(LineSeries::new(cumulative.clone(), &RED))?;
+        chart.draw_series(cumulative.into_iter().map(|p| Circle::new(p, 3, RED.filled())))?;
+
+        root.present()?;
+        Ok(())
+    }
+
     #[cfg(feature = "plot")]
-    pub fn plot_2d(&self, outfile: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn plot_2d(&self, outfile: &str) -> Result<(), PcaTreeError> {
         use plotters::prelude::*;
 
+        if self.coords.ncols() < 2 {
+            return Err(PcaTreeError::Plot("need at least 2 PCA components".to_string()));
+        }
+
         let root = BitMapBackend::new(outfile, (900, 900)).into_drawing_area();
         root.fill(&WHITE)?;
 
@@ -185,3 +640,50 @@ impl PcaModel {
     }
 }
 
+/// (Weighted) covariance of already centered rows.
+fn covariance(centered: &Array2<f32>, weights: Option<ArrayView1<f32>>) -> Array2<f32> {
+    // n == 1 has no variance; avoid the 0 / 0 of the unbiased estimator
+    match weights {
+        None => centered.t().dot(centered) / (centered.nrows().max(2) as f32 - 1.0),
+        Some(w) => {
+            let scaled = centered * &w.mapv(f32::sqrt).insert_axis(Axis(1));
+            scaled.t().dot(&scaled) / (w.sum().max(2.0) - 1.0)
+        }
+    }
+}
+
+/// Covariance around `center` from the weighted column sums and Gram matrix of a sparse matrix.
+fn sparse_covariance(sum: &Array1<f32>, gram: &Array2<f32>, center: &Array1<f32>, total: f32) -> Array2<f32> {
+    // Σ w (x - c)(x - c)ᵀ = G - s cᵀ - c sᵀ + W c cᵀ
+    let s = sum.view().insert_axis(Axis(1));
+    let c = center.view().insert_axis(Axis(1));
+    let scatter = gram - &s.dot(&c.t()) - &c.dot(&s.t()) + &(c.dot(&c.t()) * total);
+    scatter / (total.max(2.0) - 1.0)
+}
+
+/// `A Aᵀ` for `A = diag(scale) (X - 1 cᵀ)`, from the sparse row Gram matrix:
+/// `(X - 1 cᵀ)(X - 1 cᵀ)ᵀ = X Xᵀ - u 1ᵀ - 1 uᵀ + cᵀc` with `u = X c`.
+fn centered_row_gram(x: &CsrMatrix, center: &Array1<f32>, scale: ArrayView1<f32>) -> Array2<f32> {
+    let mut gram = x.row_gram();
+    let u = x.dot(&center.view().insert_axis(Axis(1)).to_owned()).remove_axis(Axis(1));
+    let cc = center.dot(center);
+    for ((i, j), g) in gram.indexed_iter_mut() {
+        *g = scale[i] * scale[j] * (*g - u[i] - u[j] + cc);
+    }
+    gram
+}
+
+/// Eigenvalues of a symmetric matrix in decreasing order (rounding noise below 0 clamped
+/// to 0) and the matching eigenvectors as columns.
+pub(crate) fn sorted_eigh(cov: Array2<f32>) -> Result<(Array1<f32>, Array2<f32>), PcaTreeError> {
+    if !cov.iter().all(|v| v.is_finite()) {
+        return Err(PcaTreeError::NonFinite("covariance matrix"));
+    }
+    let (eigvals, eigvecs) = cov.eigh(UPLO::Upper)?;
+
+    let mut idx: Vec<_> = (0..eigvals.len()).collect();
+    idx.sort_by(|a, b| eigvals[*b].total_cmp(&eigvals[*a]));
+
+    let sorted = idx.iter().map(|&i| eigvals[i].max(0.0)).collect();
+    Ok((sorted, eigvecs.select(Axis(1), &idx)))
+}
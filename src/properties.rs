@@ -0,0 +1,210 @@
+use crate::encoder::{aligned_len, channel_names};
+use crate::{PcaTreeError, SavedEncoder, SequenceEncoder};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+/// Numeric descriptor set used per amino-acid residue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AaProperties {
+    /// 5 Atchley factors (Atchley et al. 2005, PNAS 102:6395).
+    Atchley,
+    /// 10 Kidera factors (Kidera et al. 1985, J Protein Chem 4:23).
+    Kidera,
+    /// The residue's BLOSUM62 row (20 values, ARNDCQEGHILKMFPSTWYV order).
+    Blosum62,
+}
+
+impl AaProperties {
+    /// Values per residue.
+    pub fn dim(&self) -> usize {
+        match self {
+            AaProperties::Atchley => 5,
+            AaProperties::Kidera => 10,
+            AaProperties::Blosum62 => 20,
+        }
+    }
+
+    fn table(&self) -> &'static [&'static [f32]] {
+        match self {
+            AaProperties::Atchley => ATCHLEY,
+            AaProperties::Kidera => KIDERA,
+            AaProperties::Blosum62 => BLOSUM62,
+        }
+    }
+
+    /// Descriptor row of one standard residue.
+    fn row(&self, aa: u8) -> Option<&'static [f32]> {
+        ORDER.iter().position(|&a| a == aa).map(|i| self.table()[i])
+    }
+
+    /// Mean descriptor of several residues (ambiguity codes, unknowns).
+    fn mean_of(&self, residues: &[u8]) -> Vec<f32> {
+        let mut out = vec![0.0; self.dim()];
+        for &r in residues {
+            if let Some(row) = self.row(r) {
+                for (o, v) in out.iter_mut().zip(row) {
+                    *o += v / residues.len() as f32;
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Encodes aligned protein sequences as per-position residue descriptors,
+/// so that PCA distances follow substitution similarity instead of plain identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyEncoder {
+    pub properties: AaProperties,
+}
+
+impl PropertyEncoder {
+    pub fn new(properties: AaProperties) -> Self {
+        Self { properties }
+    }
+
+    /// Encode into `n × (d + 1)L` features: the `d` descriptor values followed by a gap flag.
+    ///
+    /// Gaps, `X` and stop codons get the mean descriptor of all 20 residues
+    /// (gaps additionally set the flag); B, Z and J the mean of the residues they stand for.
+    pub fn encode_batch(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        let len = aligned_len(sequences)?;
+        let d = self.properties.dim();
+        let width = d + 1;
+
+        let unknown = self.properties.mean_of(ORDER);
+
+        let mut x = Array2::<f32>::zeros((sequences.len(), width * len));
+
+        for (i, seq) in sequences.iter().enumerate() {
+            for (pos, &aa) in seq.as_bytes().iter().enumerate() {
+                let aa = aa.to_ascii_uppercase();
+                let mut gap = 0.0;
+
+                let values: Vec<f32> = match aa {
+                    b'-' | b'.' => {
+                        gap = 1.0;
+                        unknown.clone()
+                    }
+                    b'X' | b'*' => unknown.clone(),
+                    b'B' => self.properties.mean_of(b"DN"),
+                    b'Z' => self.properties.mean_of(b"EQ"),
+                    b'J' => self.properties.mean_of(b"IL"),
+                    _ => match self.properties.row(aa) {
+                        Some(row) => row.to_vec(),
+                        None => return Err(PcaTreeError::InvalidSymbol {
+                            index: i,
+                            position: pos,
+                            symbol: aa as char,
+                        }),
+                    },
+                };
+
+                let base = width * pos;
+                for (c, v) in values.iter().enumerate() {
+                    x[[i, base + c]] = *v;
+                }
+                x[[i, base + d]] = gap;
+            }
+        }
+
+        Ok(x)
+    }
+}
+
+impl SequenceEncoder for PropertyEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        self.encode_batch(sequences)
+    }
+
+    fn feature_names(&self, len: usize) -> Vec<String> {
+        let mut channels: Vec<String> = match self.properties {
+            AaProperties::Atchley => (1..=5).map(|f| format!("atchley{f}")).collect(),
+            AaProperties::Kidera => (1..=10).map(|f| format!("kidera{f}")).collect(),
+            AaProperties::Blosum62 => ORDER.iter().map(|&a| format!("blosum_{}", a as char)).collect(),
+        };
+        channels.push("gap".to_string());
+        channel_names(len, &channels)
+    }
+
+    fn channels_per_position(&self) -> Option<usize> {
+        Some(self.properties.dim() + 1)
+    }
+
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::Properties(self.clone()))
+    }
+}
+
+/// Row order of all tables below.
+const ORDER: &[u8] = b"ARNDCQEGHILKMFPSTWYV";
+
+const ATCHLEY: &[&[f32]] = &[
+    &[-0.591, -1.302, -0.733,  1.570, -0.146], // A
+    &[ 1.538, -0.055,  1.502,  0.440,  2.897], // R
+    &[ 0.945,  0.828,  1.299, -0.169,  0.933], // N
+    &[ 1.050,  0.302, -3.656, -0.259, -3.242], // D
+    &[-1.343,  0.465, -0.862, -1.020, -0.255], // C
+    &[ 0.931, -0.179, -3.005, -0.503, -1.853], // Q
+    &[ 1.357, -1.453,  1.477,  0.113, -0.837], // E
+    &[-0.384,  1.652,  1.330,  1.045,  2.064], // G
+    &[ 0.336, -0.417, -1.673, -1.474, -0.078], // H
+    &[-1.239, -0.547,  2.131,  0.393,  0.816], // I
+    &[-1.019, -0.987, -1.505,  1.266, -0.912], // L
+    &[ 1.831, -0.561,  0.533, -0.277,  1.648], // K
+    &[-0.663, -1.524,  2.219, -1.005,  1.212], // M
+    &[-1.006, -0.590,  1.891, -0.397,  0.412], // F
+    &[ 0.189,  2.081, -1.628,  0.421, -1.392], // P
+    &[-0.228,  1.399, -4.760,  0.670, -2.647], // S
+    &[-0.032,  0.326,  2.213,  0.908,  1.313], // T
+    &[-0.595,  0.009,  0.672, -2.128, -0.184], // W
+    &[ 0.260,  0.830,  3.097, -0.838,  1.512], // Y
+    &[-1.337, -0.279, -0.544,  1.242, -1.262], // V
+];
+
+const KIDERA: &[&[f32]] = &[
+    &[-1.56, -1.67, -0.97, -0.27, -0.93, -0.78, -0.20, -0.08,  0.21, -0.48], // A
+    &[ 0.22,  1.27,  1.37,  1.87, -1.70,  0.46,  0.92, -0.39,  0.23,  0.93], // R
+    &[ 1.14, -0.07, -0.12,  0.81,  0.18,  0.37, -0.09,  1.23,  1.10, -1.73], // N
+    &[ 0.58, -0.22, -1.58,  0.81, -0.92,  0.15, -1.52,  0.47,  0.76,  0.70], // D
+    &[ 0.12, -0.89,  0.45, -1.05, -0.71,  2.41,  1.52, -0.69,  1.13,  1.10], // C
+    &[-0.47,  0.24,  0.07,  1.10,  1.10,  0.59,  0.84, -0.71, -0.03, -2.33], // Q
+    &[-1.45,  0.19, -1.61,  1.17, -1.31,  0.40,  0.04,  0.38, -0.35, -0.12], // E
+    &[ 1.46, -1.96, -0.23, -0.16,  0.10, -0.11,  1.32,  2.36, -1.66,  0.46], // G
+    &[-0.41,  0.52, -0.28,  0.28,  1.61,  1.01, -1.85,  0.47,  1.13,  1.63], // H
+    &[-0.73, -0.16,  1.79, -0.77, -0.54,  0.03, -0.83,  0.51,  0.66, -1.78], // I
+    &[-1.04,  0.00, -0.24, -1.10, -0.55, -2.05,  0.96, -0.76,  0.45,  0.93], // L
+    &[-0.34,  0.82, -0.23,  1.70,  1.54, -1.62,  1.15, -0.08, -0.48,  0.60], // K
+    &[-1.40,  0.18, -0.42, -0.73,  2.00,  1.52,  0.26,  0.11, -1.27,  0.27], // M
+    &[-0.21,  0.98, -0.36, -1.43,  0.22, -0.81,  0.67,  1.10,  1.71, -0.44], // F
+    &[ 2.06, -0.33, -1.15, -0.75,  0.88, -0.45,  0.30, -2.30,  0.74, -0.28], // P
+    &[ 0.81, -1.08,  0.16,  0.42, -0.21, -0.43, -1.89, -1.15, -0.97, -0.23], // S
+    &[ 0.26, -0.70,  1.21,  0.63, -0.10,  0.21,  0.24, -1.15, -0.56,  0.19], // T
+    &[ 0.30,  2.10, -0.72, -1.57, -1.16,  0.57, -0.48, -0.40, -2.30, -0.60], // W
+    &[ 1.38,  1.48,  0.80, -0.56,  0.00, -0.68, -0.31,  1.03, -0.05,  0.53], // Y
+    &[-0.74, -0.71,  2.04, -0.40,  0.50, -0.81, -1.07,  0.06, -0.46,  0.65], // V
+];
+
+const BLOSUM62: &[&[f32]] = &[
+    //  A    R    N    D    C    Q    E    G    H    I    L    K    M    F    P    S    T    W    Y    V
+    &[ 4., -1., -2., -2.,  0., -1., -1.,  0., -2., -1., -1., -1., -1., -2., -1.,  1.,  0., -3., -2.,  0.], // A
+    &[-1.,  5.,  0., -2., -3.,  1.,  0., -2.,  0., -3., -2.,  2., -1., -3., -2., -1., -1., -3., -2., -3.], // R
+    &[-2.,  0.,  6.,  1., -3.,  0.,  0.,  0.,  1., -3., -3.,  0., -2., -3., -2.,  1.,  0., -4., -2., -3.], // N
+    &[-2., -2.,  1.,  6., -3.,  0.,  2., -1., -1., -3., -4., -1., -3., -3., -1.,  0., -1., -4., -3., -3.], // D
+    &[ 0., -3., -3., -3.,  9., -3., -4., -3., -3., -1., -1., -3., -1., -2., -3., -1., -1., -2., -2., -1.], // C
+    &[-1.,  1.,  0.,  0., -3.,  5.,  2., -2.,  0., -3., -2.,  1.,  0., -3., -1.,  0., -1., -2., -1., -2.], // Q
+    &[-1.,  0.,  0.,  2., -4.,  2.,  5., -2.,  0., -3., -3.,  1., -2., -3., -1.,  0., -1., -3., -2., -2.], // E
+    &[ 0., -2.,  0., -1., -3., -2., -2.,  6., -2., -4., -4., -2., -3., -3., -2.,  0., -2., -2., -3., -3.], // G
+    &[-2.,  0.,  1., -1., -3.,  0.,  0., -2.,  8., -3., -3., -1., -2., -1., -2., -1., -2., -2.,  2., -3.], // H
+    &[-1., -3., -3., -3., -1., -3., -3., -4., -3.,  4.,  2., -3.,  1.,  0., -3., -2., -1., -3., -1.,  3.], // I
+    &[-1., -2., -3., -4., -1., -2., -3., -4., -3.,  2.,  4., -2.,  2.,  0., -3., -2., -1., -2., -1.,  1.], // L
+    &[-1.,  2.,  0., -1., -3.,  1.,  1., -2., -1., -3., -2.,  5., -1., -3., -1.,  0., -1., -3., -2., -2.], // K
+    &[-1., -1., -2., -3., -1.,  0., -2., -3., -2.,  1.,  2., -1.,  5.,  0., -2., -1., -1., -1., -1.,  1.], // M
+    &[-2., -3., -3., -3., -2., -3., -3., -3., -1.,  0.,  0., -3.,  0.,  6., -4., -2., -2.,  1.,  3., -1.], // F
+    &[-1., -2., -2., -1., -3., -1., -1., -2., -2., -3., -3., -1., -2., -4.,  7., -1., -1., -4., -3., -2.], // P
+    &[ 1., -1.,  1.,  0., -1.,  0.,  0.,  0., -1., -2., -2.,  0., -1., -2., -1.,  4.,  1., -3., -2., -2.], // S
+    &[ 0., -1.,  0., -1., -1., -1., -1., -2., -2., -1., -1., -1., -1., -2., -1.,  1.,  5., -2., -2.,  0.], // T
+    &[-3., -3., -4., -4., -2., -2., -3., -2., -2., -3., -2., -3., -1.,  1., -4., -3., -2., 11.,  2., -3.], // W
+    &[-2., -2., -2., -3., -2., -1., -2., -3.,  2., -1., -1., -2., -1.,  3., -3., -2., -2.,  2.,  7., -1.], // Y
+    &[ 0., -3., -3., -3., -1., -2., -2., -3., -3.,  3.,  1., -2.,  1., -1., -2., -2.,  0., -3., -1.,  4.], // V
+];
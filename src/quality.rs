@@ -0,0 +1,182 @@
+use crate::{CsrMatrix, PcaTreeError};
+use ndarray::{s, Array2};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+/// How Phred qualities (FASTQ) weight the features of each base.
+///
+/// The features of a base with weight `w` are blended as `w · base + (1 - w) · consensus`,
+/// where the consensus is the most common encoded vector of that position among the
+/// confident calls. Uncertain calls are thus pulled towards the consensus instead of adding
+/// spurious mutations, in every per-position encoding: a masked base (weight 0) counts as
+/// the consensus base, not as a symbol of its own. Gaps and sequences without qualities
+/// keep weight 1, and so do bases with `1 - w` below 0.001 (Phred 30 and up with
+/// `Probabilistic`), whose blending would change nothing but the sparsity.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum QualityWeighting {
+    /// Bases below this Phred score are masked (weight 0), all others count fully.
+    Threshold(u8),
+    /// Weight by the probability that the call is correct, `1 - 10^(-Q/10)`.
+    /// Every base below Phred 30 is blended, which fills in all channels of its position in
+    /// a sparse matrix; with many such bases `Threshold` keeps `--sparse` worthwhile.
+    Probabilistic,
+}
+
+impl QualityWeighting {
+    /// Weight of one base with Phred score `q`.
+    pub fn weight(&self, q: u8) -> f32 {
+        match self {
+            QualityWeighting::Threshold(min) => if q >= *min { 1.0 } else { 0.0 },
+            QualityWeighting::Probabilistic => 1.0 - 10f32.powf(-(q as f32) / 10.0),
+        }
+    }
+
+    /// Per-column weights of `encoded`, a possibly gapped (aligned) version of `original`
+    /// whose Phred+33 qualities are `qual`.
+    pub(crate) fn column_weights(
+        &self,
+        encoded: &str,
+        original: &str,
+        qual: &str,
+    ) -> Result<Vec<f32>, PcaTreeError> {
+        let mut weights = Vec::with_capacity(original.len());
+        for (&b, &q) in original.as_bytes().iter().zip(qual.as_bytes()) {
+            if is_gap(b) {
+                continue;
+            }
+            let Some(phred) = q.checked_sub(33) else {
+                return Err(PcaTreeError::InvalidFormat(format!(
+                    "Invalid Phred+33 quality character {:?}",
+                    q as char
+                )));
+            };
+            weights.push(self.weight(phred));
+        }
+
+        // the aligner only inserts gaps, so bases still come in their original order
+        let mut bases = weights.into_iter();
+        Ok(encoded
+            .bytes()
+            .map(|b| if is_gap(b) { 1.0 } else { bases.next().unwrap_or(1.0) })
+            .collect())
+    }
+}
+
+/// Bases whose weight is this close to 1 are not blended.
+const NEGLIGIBLE: f32 = 1e-3;
+
+fn is_gap(b: u8) -> bool {
+    matches!(b, b'-' | b'.')
+}
+
+/// Blend the `channels` features of every position of row `i` with its weight in
+/// `weights[i]` towards the consensus vector of that position, see [`QualityWeighting`].
+pub(crate) fn blend_dense(x: &mut Array2<f32>, weights: &[Option<Vec<f32>>], channels: usize) {
+    let width = x.ncols();
+    let consensus = consensus_blocks(weights, channels, width, |i, j| {
+        x.slice(s![i, channels * j..channels * (j + 1)]).to_vec()
+    });
+    for (i, w) in weights.iter().enumerate() {
+        let Some(w) = w else { continue };
+        for (j, wj) in uncertain(w, channels, width) {
+            let target = &consensus[&j];
+            let mut block = x.slice_mut(s![i, channels * j..channels * (j + 1)]);
+            for (v, t) in block.iter_mut().zip(target) {
+                *v += (1.0 - wj) * (t - *v);
+            }
+        }
+    }
+}
+
+/// Sparse counterpart of [`blend_dense`]. Entries are only added where a base differs
+/// from the consensus, so one-hot and relative encodings stay sparse.
+pub(crate) fn blend_sparse(x: &CsrMatrix, weights: &[Option<Vec<f32>>], channels: usize) -> CsrMatrix {
+    let width = x.ncols();
+    let block = |i: usize, j: usize| {
+        let (cols, vals) = x.row(i);
+        let lo = cols.partition_point(|&c| c < channels * j);
+        let hi = cols.partition_point(|&c| c < channels * (j + 1));
+        let mut v = vec![0.0; channels];
+        for (&c, &val) in cols[lo..hi].iter().zip(&vals[lo..hi]) {
+            v[c - channels * j] = val;
+        }
+        v
+    };
+    let consensus = consensus_blocks(weights, channels, width, block);
+
+    let mut out = CsrMatrix::new(width);
+    for (i, w) in weights.iter().enumerate() {
+        let (cols, vals) = x.row(i);
+        let Some(w) = w else {
+            out.push_row(cols.iter().copied().zip(vals.iter().copied()));
+            continue;
+        };
+        let blended: HashMap<usize, f32> = uncertain(w, channels, width).collect();
+        let mut row: Vec<(usize, f32)> = cols
+            .iter()
+            .copied()
+            .zip(vals.iter().copied())
+            .filter(|(c, _)| !blended.contains_key(&(c / channels)))
+            .collect();
+        for (&j, &wj) in &blended {
+            for (k, (v, t)) in block(i, j).into_iter().zip(&consensus[&j]).enumerate() {
+                row.push((channels * j + k, v + (1.0 - wj) * (t - v)));
+            }
+        }
+        out.push_row(row);
+    }
+    out
+}
+
+/// Positions (that exist in `width` columns) with a weight noticeably below 1.
+fn uncertain(weights: &[f32], channels: usize, width: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+    weights
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(move |&(j, w)| 1.0 - w >= NEGLIGIBLE && channels * (j + 1) <= width)
+}
+
+/// Consensus vector of every position some row has an uncertain call at: the most common
+/// `block(row, position)` among rows with a certain call there (all rows if there are
+/// none), the first seen one on ties.
+fn consensus_blocks<F>(
+    weights: &[Option<Vec<f32>>],
+    channels: usize,
+    width: usize,
+    block: F,
+) -> HashMap<usize, Vec<f32>>
+where
+    F: Fn(usize, usize) -> Vec<f32>,
+{
+    let positions: BTreeSet<usize> = weights
+        .iter()
+        .flatten()
+        .flat_map(|w| uncertain(w, channels, width).map(|(j, _)| j))
+        .collect();
+
+    positions
+        .into_iter()
+        .map(|j| {
+            let certain = |i: &usize| {
+                weights[*i].as_ref().is_none_or(|w| w.get(j).is_none_or(|w| 1.0 - *w < NEGLIGIBLE))
+            };
+            let mut rows: Vec<usize> = (0..weights.len()).filter(certain).collect();
+            if rows.is_empty() {
+                rows = (0..weights.len()).collect();
+            }
+            // count by bit pattern; remember the first row of every vector
+            let mut counts: HashMap<Vec<u32>, (usize, usize)> = HashMap::new();
+            for i in rows {
+                let key = block(i, j).iter().map(|v| v.to_bits()).collect();
+                counts.entry(key).or_insert((0, i)).0 += 1;
+            }
+            let (_, (_, row)) = counts
+                .into_iter()
+                .max_by_key(|(_, (count, first))| (*count, Reverse(*first)))
+                .expect("at least one row");
+            (j, block(row, j))
+        })
+        .collect()
+}
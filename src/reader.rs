@@ -0,0 +1,163 @@
+use crate::PcaTreeError;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// One input sequence together with its identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeqRecord {
+    pub id: String,
+    pub seq: String,
+    /// Raw Phred+33 quality string (FASTQ only).
+    pub qual: Option<String>,
+}
+
+impl SeqRecord {
+    pub fn new(id: impl Into<String>, seq: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            seq: seq.into(),
+            qual: None,
+        }
+    }
+}
+
+/// Supported sequence file layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeqFormat {
+    Fasta,
+    Fastq,
+    /// One sequence per line, ids are the 0-based line index.
+    Plain,
+}
+
+/// Read FASTA, FASTQ or plain (one sequence per line) files.
+/// gzip compressed input is detected from the magic bytes, not the file name.
+pub fn read_sequences<P: AsRef<Path>>(path: P) -> Result<Vec<SeqRecord>, PcaTreeError> {
+    parse_sequences(open_reader(path)?)
+}
+
+/// Write sequences as FASTA, one line per sequence.
+pub fn write_fasta<P: AsRef<Path>>(path: P, ids: &[String], seqs: &[String]) -> std::io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    for (id, seq) in ids.iter().zip(seqs) {
+        writeln!(w, ">{id}")?;
+        writeln!(w, "{seq}")?;
+    }
+    Ok(())
+}
+
+/// Open a plain or gzip compressed file; gzip is detected from the magic bytes.
+pub(crate) fn open_reader<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>, PcaTreeError> {
+    let path = path.as_ref();
+    let f = File::open(path).map_err(|e| {
+        PcaTreeError::Io(std::io::Error::new(e.kind(), format!("Could not open {}: {e}", path.display())))
+    })?;
+
+    let mut buf = BufReader::new(f);
+    let is_gz = buf.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+
+    if is_gz {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(buf))))
+    } else {
+        Ok(Box::new(buf))
+    }
+}
+
+/// Parse sequences from any buffered reader, guessing the format from the first record.
+///
+/// Blank lines are skipped in FASTA and plain input. In FASTQ they are only skipped
+/// before the first and after the last record, since an empty read has empty sequence
+/// and quality lines.
+pub fn parse_sequences<R: BufRead>(reader: R) -> Result<Vec<SeqRecord>, PcaTreeError> {
+    let lines: Vec<String> = reader
+        .lines()
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .map(|l| l.trim_end().to_string())
+        .collect();
+    let non_empty = || lines.iter().filter(|l| !l.is_empty()).cloned().collect::<Vec<_>>();
+
+    match guess_format(&lines) {
+        SeqFormat::Fasta => parse_fasta(&non_empty()),
+        SeqFormat::Fastq => {
+            let start = lines.iter().position(|l| !l.is_empty()).unwrap_or(0);
+            let mut end = lines.iter().rposition(|l| !l.is_empty()).map_or(start, |e| e + 1);
+            // trailing blank lines may still be the empty read and quality of the last record
+            while !(end - start).is_multiple_of(4) && lines.get(end).is_some_and(|l| l.is_empty()) {
+                end += 1;
+            }
+            parse_fastq(&lines[start..end])
+        }
+        SeqFormat::Plain => Ok(non_empty()
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| SeqRecord::new(i.to_string(), s))
+            .collect()),
+    }
+}
+
+fn guess_format(lines: &[String]) -> SeqFormat {
+    match lines.iter().find(|l| !l.is_empty()).and_then(|l| l.chars().next()) {
+        Some('>') => SeqFormat::Fasta,
+        Some('@') => SeqFormat::Fastq,
+        _ => SeqFormat::Plain,
+    }
+}
+
+/// Header up to the first whitespace, like most aligners report it.
+fn header_id(line: &str) -> String {
+    line[1..].split_whitespace().next().unwrap_or_default().to_string()
+}
+
+fn parse_fasta(lines: &[String]) -> Result<Vec<SeqRecord>, PcaTreeError> {
+    let mut out: Vec<SeqRecord> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.starts_with('>') {
+            out.push(SeqRecord::new(header_id(line), String::new()));
+        } else if let Some(rec) = out.last_mut() {
+            // multi-line FASTA: concatenate
+            rec.seq.push_str(line.trim());
+        } else {
+            return Err(PcaTreeError::InvalidFormat(format!(
+                "FASTA line {} outside of a record", i + 1
+            )));
+        }
+    }
+
+    Ok(out)
+}
+
+fn parse_fastq(lines: &[String]) -> Result<Vec<SeqRecord>, PcaTreeError> {
+    if !lines.len().is_multiple_of(4) {
+        return Err(PcaTreeError::InvalidFormat(format!(
+            "Truncated FASTQ: {} lines is not a multiple of 4",
+            lines.len()
+        )));
+    }
+
+    let mut out = Vec::with_capacity(lines.len() / 4);
+
+    for (r, chunk) in lines.chunks(4).enumerate() {
+        if !chunk[0].starts_with('@') || !chunk[2].starts_with('+') {
+            return Err(PcaTreeError::InvalidFormat(format!(
+                "Malformed FASTQ record {}", r + 1
+            )));
+        }
+        if chunk[1].len() != chunk[3].len() {
+            return Err(PcaTreeError::InvalidFormat(format!(
+                "FASTQ record {}: sequence and quality length differ",
+                r + 1
+            )));
+        }
+        out.push(SeqRecord {
+            id: header_id(&chunk[0]),
+            seq: chunk[1].clone(),
+            qual: Some(chunk[3].clone()),
+        });
+    }
+
+    Ok(out)
+}
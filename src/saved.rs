@@ -0,0 +1,236 @@
+use crate::fitted::{AlignmentLayout, FittedEncoder};
+use crate::reader::open_reader;
+use crate::{
+    Aligner, AminoAcidEncoder, CodonEncoder, Distance, Encoding, KmerEncoder, MstTree, OneHotEncoder,
+    PcaModel, PcaTree, PcaTreeError, PositionMask, PropertyEncoder, QualityWeighting, RelativeEncoder,
+    SequenceEncoder, SubstitutionEncoder,
+};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Version of the model files written by [`PcaTree::save`]; files of a newer version are rejected.
+pub const MODEL_FORMAT_VERSION: u32 = 1;
+
+/// Tag in every model file, to reject other JSON early.
+const MODEL_FORMAT: &str = "pca_tree";
+
+/// A built-in encoder in serialisable form, see [`SequenceEncoder::saved`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SavedEncoder {
+    Encoding(Encoding),
+    OneHot,
+    AminoAcid,
+    Relative(RelativeEncoder),
+    Properties(PropertyEncoder),
+    Substitution(SubstitutionEncoder),
+    Codon(CodonEncoder),
+    Kmer(KmerEncoder),
+}
+
+impl SavedEncoder {
+    /// The encoder described by this value.
+    pub fn into_encoder(self) -> Arc<dyn SequenceEncoder> {
+        match self {
+            SavedEncoder::Encoding(e) => Arc::new(e),
+            SavedEncoder::OneHot => Arc::new(OneHotEncoder::new()),
+            SavedEncoder::AminoAcid => Arc::new(AminoAcidEncoder::new()),
+            SavedEncoder::Relative(e) => Arc::new(e),
+            SavedEncoder::Properties(e) => Arc::new(e),
+            SavedEncoder::Substitution(e) => Arc::new(e),
+            SavedEncoder::Codon(e) => Arc::new(e),
+            SavedEncoder::Kmer(e) => Arc::new(e),
+        }
+    }
+}
+
+/// Everything a fitted [`PcaTree`] holds, as written to disk.
+#[derive(Serialize, Deserialize)]
+struct ModelFile {
+    format: String,
+    version: u32,
+    ids: Vec<String>,
+    seqs: Vec<String>,
+    quals: Vec<Option<String>>,
+    features: Vec<String>,
+    alignment: Option<Vec<String>>,
+    positions: Option<Vec<usize>>,
+    counts: Vec<usize>,
+    members: Option<Vec<Vec<String>>>,
+    encoder: SavedEncoder,
+    aligner: Option<Aligner>,
+    mask: Option<PositionMask>,
+    quality: Option<QualityWeighting>,
+    sparse: bool,
+    distance: Distance,
+    germline: bool,
+    pca: PcaModel,
+    edges: Vec<(usize, usize, f32)>,
+    /// State of the [`FittedEncoder`].
+    fitted_encoder: SavedEncoder,
+    fitted_alignment: Option<AlignmentLayout>,
+}
+
+impl PcaTree {
+    /// Write the fitted model as versioned JSON, gzip compressed if `path` ends in `.gz`:
+    /// configuration, sequences, PCA, tree and the encoder state [`PcaTree::transform`] needs.
+    ///
+    /// Custom encoders without a [`SequenceEncoder::saved`] form cannot be saved.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PcaTreeError> {
+        let fitted = self.fitted_encoder.as_ref().ok_or(PcaTreeError::NotFitted)?;
+        let saved = |e: &dyn SequenceEncoder| e.saved().ok_or(PcaTreeError::NotSaveable("custom encoder"));
+
+        let file = ModelFile {
+            format: MODEL_FORMAT.to_string(),
+            version: MODEL_FORMAT_VERSION,
+            ids: self.ids.clone(),
+            seqs: self.seqs.clone(),
+            quals: self.quals.clone(),
+            features: self.features.clone(),
+            alignment: self.alignment.clone(),
+            positions: self.positions.clone(),
+            counts: self.counts.clone(),
+            members: self.members.clone(),
+            encoder: saved(self.encoder.as_ref())?,
+            aligner: self.aligner.clone(),
+            mask: self.mask.clone(),
+            quality: self.quality,
+            sparse: self.sparse,
+            distance: self.distance,
+            germline: self.germline,
+            pca: self.pca.clone(),
+            edges: self.tree.edges.clone(),
+            fitted_encoder: saved(fitted.encoder.as_ref())?,
+            fitted_alignment: fitted.alignment.clone(),
+        };
+
+        let path = path.as_ref();
+        let out = BufWriter::new(File::create(path)?);
+        if path.extension().is_some_and(|e| e == "gz") {
+            let mut gz = GzEncoder::new(out, Compression::default());
+            serde_json::to_writer(&mut gz, &file).map_err(std::io::Error::from)?;
+            gz.finish()?.flush()?;
+        } else {
+            let mut out = out;
+            serde_json::to_writer(&mut out, &file).map_err(std::io::Error::from)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Read a model written by [`PcaTree::save`] (plain or gzip compressed), ready to
+    /// [`transform`](PcaTree::transform) new sequences. Files whose ids, coordinates, tree,
+    /// PCA and features do not fit together are rejected with [`PcaTreeError::InvalidFormat`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PcaTree, PcaTreeError> {
+        let invalid = |e: serde_json::Error| PcaTreeError::InvalidFormat(format!("Invalid model file: {e}"));
+
+        let value: serde_json::Value = serde_json::from_reader(open_reader(path)?).map_err(invalid)?;
+        if value.get("format").and_then(|f| f.as_str()) != Some(MODEL_FORMAT) {
+            return Err(PcaTreeError::InvalidFormat("Not a pca_tree model file".to_string()));
+        }
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(v) if v <= MODEL_FORMAT_VERSION as u64 => {}
+            v => {
+                return Err(PcaTreeError::InvalidFormat(format!(
+                    "Unsupported model file version {}, this build reads up to {MODEL_FORMAT_VERSION}",
+                    v.map_or("?".to_string(), |v| v.to_string())
+                )));
+            }
+        }
+        let file: ModelFile = serde_json::from_value(value).map_err(invalid)?;
+        let fitted = file.fitted_encoder.clone().into_encoder();
+        file.check(fitted.as_ref())?;
+
+        let fitted_encoder = FittedEncoder::new(
+            fitted,
+            file.fitted_alignment,
+            file.positions.clone(),
+            file.features.clone(),
+        );
+        Ok(PcaTree {
+            pca: file.pca,
+            tree: MstTree { edges: file.edges },
+            ids: file.ids,
+            features: file.features,
+            alignment: file.alignment,
+            positions: file.positions,
+            counts: file.counts,
+            members: file.members,
+            seqs: file.seqs,
+            quals: file.quals,
+            encoder: file.encoder.into_encoder(),
+            aligner: file.aligner,
+            mask: file.mask,
+            quality: file.quality,
+            sparse: file.sparse,
+            distance: file.distance,
+            germline: file.germline,
+            fitted: true,
+            fitted_encoder: Some(fitted_encoder),
+        })
+    }
+}
+
+impl ModelFile {
+    /// Reject files whose parts do not describe the same model, e.g. edited by hand
+    /// or pieced together from different fits.
+    fn check(&self, fitted: &dyn SequenceEncoder) -> Result<(), PcaTreeError> {
+        let fail = |what: String| Err(PcaTreeError::InvalidFormat(format!("Inconsistent model file: {what}")));
+
+        let n = self.ids.len();
+        let rows = [
+            ("sequences", self.seqs.len()),
+            ("qualities", self.quals.len()),
+            ("counts", self.counts.len()),
+            ("coordinates", self.pca.coords.nrows()),
+            ("members", self.members.as_ref().map_or(n, Vec::len)),
+            ("aligned sequences", self.alignment.as_ref().map_or(n, Vec::len)),
+        ];
+        if let Some((what, len)) = rows.into_iter().find(|&(_, len)| len != n) {
+            return fail(format!("{n} ids but {len} {what}"));
+        }
+        if n == 0 {
+            return fail("no sequences".to_string());
+        }
+        if let Some(&(a, b, _)) = self.edges.iter().find(|(a, b, _)| *a >= n || *b >= n) {
+            return fail(format!("edge {a}-{b} between {n} nodes"));
+        }
+        if self.edges.len() != n - 1 {
+            return fail(format!("{} edges for a tree of {n} nodes", self.edges.len()));
+        }
+
+        let pca = &self.pca;
+        let p = self.features.len();
+        if pca.mean.len() != p || pca.components.nrows() != p {
+            return fail(format!(
+                "{p} features but a mean of {} and components of {} rows",
+                pca.mean.len(),
+                pca.components.nrows()
+            ));
+        }
+        if pca.coords.ncols() != pca.components.ncols() || pca.eigenvalues.len() < pca.components.ncols() {
+            return fail(format!(
+                "{} components but {} coordinate columns and {} eigenvalues",
+                pca.components.ncols(),
+                pca.coords.ncols(),
+                pca.eigenvalues.len()
+            ));
+        }
+
+        // the fitted encoder sees the masked, aligned sequences
+        let len = match (&self.positions, &self.alignment) {
+            (Some(p), _) => p.len(),
+            (None, Some(a)) => a[0].len(),
+            (None, None) => self.seqs[0].len(),
+        };
+        let expected = fitted.feature_names(len).len();
+        if expected != p {
+            return fail(format!("the encoder makes {expected} features, the PCA has {p}"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{CsrMatrix, PcaTreeError};
+use ndarray::{Array1, Array2, Axis};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Rule for choosing the number of PCA components from the eigenvalue spectrum.
+///
+/// With a rule set, [`PcaModel`](crate::PcaModel) ignores its fixed `k` and keeps the
+/// leading components the rule accepts, but always at least one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ComponentSelection {
+    /// Fewest components whose cumulative share of the variance reaches the target (0..=1).
+    Variance(f32),
+    /// Components that explain more of the variance than the broken-stick model expects
+    /// from splitting it at random.
+    BrokenStick,
+    /// Eigenvalues above the Marchenko–Pastur edge of pure noise, `σ² (1 + √(p/n))²`,
+    /// with `σ²` the mean eigenvalue. Conservative when the signal is strong.
+    MarchenkoPastur,
+    /// Horn's parallel analysis: eigenvalues above the 95th percentile of the same
+    /// eigenvalue of `permutations` copies of the data with every column shuffled.
+    ParallelAnalysis { permutations: usize, seed: u64 },
+}
+
+impl fmt::Display for ComponentSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComponentSelection::Variance(t) => write!(f, "cumulative variance >= {:.0}%", t * 100.0),
+            ComponentSelection::BrokenStick => write!(f, "broken stick"),
+            ComponentSelection::MarchenkoPastur => write!(f, "Marchenko-Pastur"),
+            ComponentSelection::ParallelAnalysis { permutations, seed } => {
+                write!(f, "parallel analysis ({permutations} permutations, seed {seed})")
+            }
+        }
+    }
+}
+
+impl ComponentSelection {
+    /// Number of leading components to keep, given all eigenvalues (largest first)
+    /// of data with `n` rows. `null` returns the sorted eigenvalues of one permuted
+    /// copy of the data and is only called for parallel analysis.
+    pub(crate) fn choose<F>(&self, eigenvalues: &Array1<f32>, n: f32, mut null: F) -> Result<usize, PcaTreeError>
+    where
+        F: FnMut(&mut SplitMix64) -> Result<Array1<f32>, PcaTreeError>,
+    {
+        let p = eigenvalues.len();
+        let total = eigenvalues.sum();
+        if p == 0 || total <= 0.0 {
+            return Ok(p.min(1));
+        }
+        let k = match *self {
+            ComponentSelection::Variance(target) => {
+                let mut acc = 0.0;
+                // every component that starts below the target, i.e. up to the one reaching it
+                leading(eigenvalues, |_, v| {
+                    let below = acc < target - 1e-6;
+                    acc += v / total;
+                    below
+                })
+            }
+            ComponentSelection::BrokenStick => {
+                // expected share of the j-th largest piece: (1/p) Σ_{i=j}^{p} 1/i
+                let mut expected = vec![0.0; p];
+                let mut tail = 0.0;
+                for j in (0..p).rev() {
+                    tail += 1.0 / (j + 1) as f32;
+                    expected[j] = tail / p as f32;
+                }
+                leading(eigenvalues, |j, v| v / total > expected[j])
+            }
+            ComponentSelection::MarchenkoPastur => {
+                let sigma2 = total / p as f32;
+                let edge = sigma2 * (1.0 + (p as f32 / n.max(1.0)).sqrt()).powi(2);
+                leading(eigenvalues, |_, v| v > edge)
+            }
+            ComponentSelection::ParallelAnalysis { permutations, seed } => {
+                let mut rng = SplitMix64::new(seed);
+                let mut nulls = Vec::with_capacity(permutations.max(1));
+                for _ in 0..permutations.max(1) {
+                    nulls.push(null(&mut rng)?);
+                }
+                let threshold: Vec<f32> = (0..p)
+                    .map(|j| {
+                        let mut col: Vec<f32> = nulls.iter().map(|e| e.get(j).copied().unwrap_or(0.0)).collect();
+                        col.sort_by(f32::total_cmp);
+                        col[(col.len() * 95).div_ceil(100) - 1]
+                    })
+                    .collect();
+                leading(eigenvalues, |j, v| v > threshold[j])
+            }
+        };
+        Ok(k.clamp(1, p))
+    }
+}
+
+/// Number of leading eigenvalues (index, value) for which `keep` holds.
+fn leading(eigenvalues: &Array1<f32>, mut keep: impl FnMut(usize, f32) -> bool) -> usize {
+    eigenvalues.iter().enumerate().take_while(|(j, v)| keep(*j, **v)).count()
+}
+
+/// splitmix64: a tiny generator so that permutations only depend on the seed,
+/// on every platform.
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut h = self.0;
+        h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        h ^ (h >> 31)
+    }
+
+    /// Uniform in `0..n` (the modulo bias is negligible for matrix sizes).
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Standard normal sample (Box–Muller).
+    pub(crate) fn normal(&mut self) -> f32 {
+        // 53 random bits in (0, 1]
+        let u1 = ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64;
+        let u2 = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        ((-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()) as f32
+    }
+
+    /// Fisher–Yates shuffle.
+    pub(crate) fn shuffle<T>(&mut self, xs: &mut [T]) {
+        for i in (1..xs.len()).rev() {
+            xs.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// `x` with the rows of every column shuffled independently.
+pub(crate) fn permute_columns(x: &Array2<f32>, rng: &mut SplitMix64) -> Array2<f32> {
+    let mut out = x.clone();
+    for mut col in out.axis_iter_mut(Axis(1)) {
+        let mut values = col.to_vec();
+        rng.shuffle(&mut values);
+        for (c, v) in col.iter_mut().zip(values) {
+            *c = v;
+        }
+    }
+    out
+}
+
+/// Sparse counterpart of [`permute_columns`]: the non-zeros of every column move to
+/// a random set of rows, so the result stays as sparse as `x`.
+pub(crate) fn permute_csr_columns(x: &CsrMatrix, rng: &mut SplitMix64) -> CsrMatrix {
+    let n = x.nrows();
+    let mut columns: Vec<Vec<f32>> = vec![Vec::new(); x.ncols()];
+    for i in 0..n {
+        let (cols, vals) = x.row(i);
+        for (&c, &v) in cols.iter().zip(vals) {
+            columns[c].push(v);
+        }
+    }
+
+    let mut rows: Vec<Vec<(usize, f32)>> = vec![Vec::new(); n];
+    for (c, mut values) in columns.into_iter().enumerate() {
+        rng.shuffle(&mut values);
+        // Floyd's sampling of values.len() distinct rows
+        let mut taken = HashSet::with_capacity(values.len());
+        for (j, v) in (n - values.len()..n).zip(values) {
+            let t = rng.below(j + 1);
+            let r = if taken.insert(t) { t } else { taken.insert(j); j };
+            rows[r].push((c, v));
+        }
+    }
+
+    let mut m = CsrMatrix::new(x.ncols());
+    for r in rows {
+        m.push_row(r);
+    }
+    m
+}
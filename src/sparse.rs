@@ -0,0 +1,191 @@
+use ndarray::{Array1, Array2, ArrayView1};
+
+/// Compressed sparse row matrix of encoded sequences (`n × p`).
+///
+/// One-hot and relative encodings are mostly zeros; storing only the non-zero
+/// entries keeps memory proportional to the number of mutations instead of `n × p`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsrMatrix {
+    ncols: usize,
+    /// Start of every row in `indices` / `data`, plus the total as last entry.
+    indptr: Vec<usize>,
+    indices: Vec<usize>,
+    data: Vec<f32>,
+}
+
+impl CsrMatrix {
+    /// Empty matrix with `ncols` columns; add rows with [`CsrMatrix::push_row`].
+    pub fn new(ncols: usize) -> Self {
+        Self {
+            ncols,
+            indptr: vec![0],
+            indices: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Append a row from `(column, value)` pairs; zeros are skipped.
+    ///
+    /// Panics if a column is out of range.
+    pub fn push_row<I: IntoIterator<Item = (usize, f32)>>(&mut self, entries: I) {
+        let mut row: Vec<(usize, f32)> = entries.into_iter().filter(|(_, v)| *v != 0.0).collect();
+        row.sort_by_key(|(c, _)| *c);
+        for (c, v) in row {
+            assert!(c < self.ncols, "column {c} out of range for {} columns", self.ncols);
+            self.indices.push(c);
+            self.data.push(v);
+        }
+        self.indptr.push(self.indices.len());
+    }
+
+    pub fn from_dense(x: &Array2<f32>) -> Self {
+        let mut m = Self::new(x.ncols());
+        for row in x.outer_iter() {
+            m.push_row(row.iter().copied().enumerate());
+        }
+        m
+    }
+
+    pub fn to_dense(&self) -> Array2<f32> {
+        let mut x = Array2::zeros((self.nrows(), self.ncols));
+        for i in 0..self.nrows() {
+            let (cols, vals) = self.row(i);
+            for (&c, &v) in cols.iter().zip(vals) {
+                x[[i, c]] = v;
+            }
+        }
+        x
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.indptr.len() - 1
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    /// Number of stored (non-zero) entries.
+    pub fn nnz(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Column indices and values of row `i`.
+    pub fn row(&self, i: usize) -> (&[usize], &[f32]) {
+        let r = self.indptr[i]..self.indptr[i + 1];
+        (&self.indices[r.clone()], &self.data[r])
+    }
+
+    /// Values of row `i`, mutable (the sparsity pattern stays fixed).
+    pub fn row_values_mut(&mut self, i: usize) -> (&[usize], &mut [f32]) {
+        let r = self.indptr[i]..self.indptr[i + 1];
+        (&self.indices[r.clone()], &mut self.data[r])
+    }
+
+    /// Row `i` as a dense vector.
+    pub fn dense_row(&self, i: usize) -> Array1<f32> {
+        let mut out = Array1::zeros(self.ncols);
+        let (cols, vals) = self.row(i);
+        for (&c, &v) in cols.iter().zip(vals) {
+            out[c] = v;
+        }
+        out
+    }
+
+    /// `self · b` for a dense `p × k` matrix.
+    pub fn dot(&self, b: &Array2<f32>) -> Array2<f32> {
+        let mut out = Array2::zeros((self.nrows(), b.ncols()));
+        for i in 0..self.nrows() {
+            let (cols, vals) = self.row(i);
+            let mut o = out.row_mut(i);
+            for (&c, &v) in cols.iter().zip(vals) {
+                o.scaled_add(v, &b.row(c));
+            }
+        }
+        out
+    }
+
+    /// `selfᵀ · b` for a dense `n × k` matrix.
+    pub fn t_dot(&self, b: &Array2<f32>) -> Array2<f32> {
+        let mut out = Array2::zeros((self.ncols, b.ncols()));
+        for i in 0..self.nrows() {
+            let (cols, vals) = self.row(i);
+            let bi = b.row(i);
+            for (&c, &v) in cols.iter().zip(vals) {
+                out.row_mut(c).scaled_add(v, &bi);
+            }
+        }
+        out
+    }
+
+    /// Weighted column sums `Σ w_i x_i` and sums of squares `Σ w_i x_i²`.
+    pub(crate) fn weighted_column_sums(&self, weights: ArrayView1<f32>) -> (Array1<f32>, Array1<f32>) {
+        let mut sum = Array1::zeros(self.ncols);
+        let mut sumsq = Array1::zeros(self.ncols);
+        for i in 0..self.nrows() {
+            let w = weights[i];
+            let (cols, vals) = self.row(i);
+            for (&c, &v) in cols.iter().zip(vals) {
+                sum[c] += w * v;
+                sumsq[c] += w * v * v;
+            }
+        }
+        (sum, sumsq)
+    }
+
+    /// Row Gram matrix `X Xᵀ` (`n × n`), in `O(n · nnz)` time and `O(n² + p)` memory.
+    pub(crate) fn row_gram(&self) -> Array2<f32> {
+        let n = self.nrows();
+        let mut gram = Array2::zeros((n, n));
+        let mut dense = vec![0.0f32; self.ncols];
+        for i in 0..n {
+            let (ci, vi) = self.row(i);
+            for (&c, &v) in ci.iter().zip(vi) {
+                dense[c] = v;
+            }
+            for j in i..n {
+                let (cj, vj) = self.row(j);
+                let dot: f32 = cj.iter().zip(vj).map(|(&c, &v)| dense[c] * v).sum();
+                gram[[i, j]] = dot;
+                gram[[j, i]] = dot;
+            }
+            for &c in ci {
+                dense[c] = 0.0;
+            }
+        }
+        gram
+    }
+
+    /// The same matrix with only the columns for which `keep` holds (the others become 0).
+    pub(crate) fn retain_columns(&self, keep: impl Fn(usize) -> bool) -> CsrMatrix {
+        let mut out = CsrMatrix::new(self.ncols);
+        for i in 0..self.nrows() {
+            let (cols, vals) = self.row(i);
+            out.push_row(cols.iter().copied().zip(vals.iter().copied()).filter(|(c, _)| keep(*c)));
+        }
+        out
+    }
+
+    /// Weighted column sums `Σ w_i x_i` and weighted Gram matrix `Σ w_i x_i x_iᵀ`.
+    pub(crate) fn weighted_moments(&self, weights: ArrayView1<f32>) -> (Array1<f32>, Array2<f32>) {
+        let mut sum = Array1::zeros(self.ncols);
+        let mut gram = Array2::zeros((self.ncols, self.ncols));
+        for i in 0..self.nrows() {
+            let w = weights[i];
+            let (cols, vals) = self.row(i);
+            for (a, (&ca, &va)) in cols.iter().zip(vals).enumerate() {
+                sum[ca] += w * va;
+                for (&cb, &vb) in cols[a..].iter().zip(&vals[a..]) {
+                    gram[[ca, cb]] += w * va * vb;
+                }
+            }
+        }
+        // only the upper triangle was filled
+        for a in 0..self.ncols {
+            for b in 0..a {
+                gram[[a, b]] = gram[[b, a]];
+            }
+        }
+        (sum, gram)
+    }
+}
@@ -0,0 +1,204 @@
+use crate::codon::translate;
+use crate::encoder::{aligned_len, channel_names, iupac_weights, normalize_base};
+use crate::{Alphabet, ColumnStats, ConsensusRules, CsrMatrix, PcaTreeError, SavedEncoder, SequenceEncoder};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+/// How much of a substitution is kept by [`SubstitutionEncoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MutationDetail {
+    /// Which base was substituted in: A, C, G, T and gap per position.
+    #[default]
+    Base,
+    /// Transition (A<->G, C<->T), transversion and gap per position.
+    TransitionTransversion,
+    /// Synonymous, non-synonymous and gap per position, translating the reference
+    /// codon in the reading frame starting at `frame` (0, 1 or 2).
+    Codon { frame: usize },
+}
+
+impl MutationDetail {
+    fn channels(&self) -> &'static [&'static str] {
+        match self {
+            MutationDetail::Base => &["A", "C", "G", "T", "gap"],
+            MutationDetail::TransitionTransversion => &["ts", "tv", "gap"],
+            MutationDetail::Codon { .. } => &["syn", "nonsyn", "gap"],
+        }
+    }
+}
+
+/// Nucleotide encoding relative to the consensus or a reference (e.g. the germline)
+/// that keeps the kind of each mutation instead of a single mismatch flag.
+///
+/// Positions matching the reference are all zero, so only mutations add variance,
+/// but two sequences with different mutations at the same site no longer look alike.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubstitutionEncoder {
+    pub detail: MutationDetail,
+    /// Compare against this sequence instead of the per-column consensus.
+    pub reference: Option<String>,
+    /// How the consensus is chosen when there is no reference.
+    pub consensus: ConsensusRules,
+    /// Positions that are always 0, like the dropped gap columns of the consensus
+    /// this encoder was frozen at.
+    pub dropped: Vec<usize>,
+}
+
+impl SubstitutionEncoder {
+    pub fn new(detail: MutationDetail) -> Self {
+        Self { detail, reference: None, consensus: ConsensusRules::default(), dropped: Vec::new() }
+    }
+
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    pub fn with_consensus(mut self, rules: ConsensusRules) -> Self {
+        self.consensus = rules;
+        self
+    }
+
+    /// Encode into `n × cL` features, `c` being the channels of the chosen detail.
+    ///
+    /// IUPAC codes spread their weight over the bases they stand for, so e.g. `R`
+    /// against a reference `A` counts as half a transition to G. With
+    /// [`MutationDetail::Codon`], each mutation is placed into the reference codon alone;
+    /// positions outside complete codons or in codons the reference leaves ambiguous
+    /// count as non-synonymous.
+    pub fn encode_batch(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        Ok(self.encode_csr(sequences)?.to_dense())
+    }
+
+    /// `encode_batch` as a sparse matrix: positions matching the reference store nothing.
+    fn encode_csr(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        if let MutationDetail::Codon { frame: frame @ 3.. } = self.detail {
+            return Err(PcaTreeError::ReadingFrame(frame));
+        }
+        let len = aligned_len(sequences)?;
+
+        let (reference, dropped) = match &self.reference {
+            Some(r) if r.len() != len => {
+                return Err(PcaTreeError::ReferenceLength { expected: len, found: r.len() });
+            }
+            Some(r) => (r.to_ascii_uppercase().into_bytes(), self.dropped.clone()),
+            None => {
+                let stats = ColumnStats::new(sequences, Alphabet::Nucleotide)?.with_rules(self.consensus);
+                (stats.consensus().into_bytes(), stats.dropped_columns())
+            }
+        };
+
+        let width = self.detail.channels().len();
+        let mut x = CsrMatrix::new(width * len);
+
+        for (i, seq) in sequences.iter().enumerate() {
+            let mut row = Vec::new();
+            for (pos, &base) in seq.as_bytes().iter().enumerate() {
+                let Some(w) = iupac_weights(base) else {
+                    return Err(PcaTreeError::InvalidSymbol {
+                        index: i,
+                        position: pos,
+                        symbol: base as char,
+                    });
+                };
+                if dropped.binary_search(&pos).is_ok() {
+                    continue;
+                }
+                let site = self.site(&reference, pos, &w);
+                row.extend(site.into_iter().enumerate().map(|(c, v)| (width * pos + c, v)));
+            }
+            x.push_row(row);
+        }
+
+        Ok(x)
+    }
+
+    /// Channel values at `pos` for a base with IUPAC weights `w` over (A, C, G, T, gap).
+    fn site(&self, reference: &[u8], pos: usize, w: &[f32; 5]) -> Vec<f32> {
+        const BASES: &[u8; 4] = b"ACGT";
+        let r = normalize_base(reference[pos]);
+
+        let mut out = vec![0.0; self.detail.channels().len()];
+        let gap = out.len() - 1;
+        out[gap] = w[4];
+
+        for (k, (b, &v)) in BASES.iter().zip(w).enumerate() {
+            if v == 0.0 || *b == r {
+                continue;
+            }
+            let c = match self.detail {
+                MutationDetail::Base => k,
+                MutationDetail::TransitionTransversion => {
+                    if is_purine(*b) == is_purine(r) { 0 } else { 1 }
+                }
+                MutationDetail::Codon { frame } => {
+                    if synonymous(reference, pos, frame, *b) { 0 } else { 1 }
+                }
+            };
+            out[c] += v;
+        }
+
+        out
+    }
+}
+
+fn is_purine(b: u8) -> bool {
+    matches!(b, b'A' | b'G')
+}
+
+/// Whether putting `base` at `pos` of the reference keeps the encoded amino acid.
+fn synonymous(reference: &[u8], pos: usize, frame: usize, base: u8) -> bool {
+    if pos < frame {
+        return false;
+    }
+    let start = pos - (pos - frame) % 3;
+    let Some(codon) = reference.get(start..start + 3) else {
+        return false;
+    };
+
+    let mut mutated = [codon[0], codon[1], codon[2]];
+    mutated[pos - start] = base;
+
+    match (translate(codon), translate(&mutated)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+impl SequenceEncoder for SubstitutionEncoder {
+    fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+        self.encode_batch(sequences)
+    }
+
+    fn encode_sparse(&self, sequences: &[String]) -> Result<CsrMatrix, PcaTreeError> {
+        self.encode_csr(sequences)
+    }
+
+    fn feature_names(&self, len: usize) -> Vec<String> {
+        channel_names(len, self.detail.channels())
+    }
+
+    fn with_reference(&self, reference: &str) -> Option<Box<dyn SequenceEncoder>> {
+        Some(Box::new(self.clone().with_reference(reference)))
+    }
+
+    fn channels_per_position(&self) -> Option<usize> {
+        Some(self.detail.channels().len())
+    }
+
+    fn freeze(&self, sequences: &[String]) -> Result<Option<Box<dyn SequenceEncoder>>, PcaTreeError> {
+        if self.reference.is_some() {
+            return Ok(None);
+        }
+        let stats = ColumnStats::new(sequences, Alphabet::Nucleotide)?.with_rules(self.consensus);
+        let frozen = SubstitutionEncoder {
+            dropped: stats.dropped_columns(),
+            ..self.clone().with_reference(stats.consensus())
+        };
+        Ok(Some(Box::new(frozen)))
+    }
+
+    fn saved(&self) -> Option<SavedEncoder> {
+        Some(SavedEncoder::Substitution(self.clone()))
+    }
+}
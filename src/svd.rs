@@ -0,0 +1,106 @@
+use crate::pca::sorted_eigh;
+use crate::select::SplitMix64;
+use crate::PcaTreeError;
+use ndarray::{s, Array1, Array2, Axis};
+use serde::{Deserialize, Serialize};
+
+/// Extra random directions beyond `k`, so the top `k` are captured accurately.
+const OVERSAMPLES: usize = 10;
+/// Power iterations; they sharpen the slowly decaying spectra of one-hot features.
+const POWER_ITERATIONS: usize = 4;
+
+/// Seed of the randomized solver unless one is given, shared by [`PcaModel`](crate::PcaModel),
+/// the builder and the command line so that they give the same components.
+pub const DEFAULT_SEED: u64 = 1;
+
+/// How [`PcaModel`](crate::PcaModel) finds the leading components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Solver {
+    /// `Randomized` if `k` is small against the data (`10 k ≤ min(n, p)` and `p ≥ 200`),
+    /// otherwise `Full`.
+    #[default]
+    Auto,
+    /// Eigen decomposition of the full `p × p` covariance matrix; all eigenvalues are known.
+    Full,
+    /// Randomized truncated SVD of the centered data (Halko, Martinsson & Tropp 2011):
+    /// never builds the covariance and only computes the top `k` components, reproducibly
+    /// for a given seed. Falls back to `Full` when `k` is not below `min(n, p)`, where a
+    /// truncated SVD saves nothing, and with a [`ComponentSelection`](crate::ComponentSelection).
+    Randomized,
+}
+
+impl Solver {
+    /// Whether to use the randomized solver for `k` components of an `n × p` matrix.
+    pub(crate) fn randomized(&self, n: usize, p: usize, k: usize) -> bool {
+        match self {
+            Solver::Auto => 10 * k <= n.min(p) && p >= 200,
+            Solver::Full => false,
+            Solver::Randomized => k < n.min(p),
+        }
+    }
+}
+
+/// Top `k` right singular vectors (as `p × k` columns) and squared singular values of an
+/// `n × p` matrix `A` that is only accessed through `mul(M) = A·M` and `tmul(Y) = Aᵀ·Y`.
+pub(crate) fn randomized_svd<F, G>(
+    n: usize,
+    p: usize,
+    k: usize,
+    seed: u64,
+    mul: F,
+    tmul: G,
+) -> Result<(Array2<f32>, Array1<f32>), PcaTreeError>
+where
+    F: Fn(&Array2<f32>) -> Array2<f32>,
+    G: Fn(&Array2<f32>) -> Array2<f32>,
+{
+    let l = (k + OVERSAMPLES).min(n).min(p);
+    let k = k.min(l);
+
+    let mut rng = SplitMix64::new(seed);
+    let omega = Array2::from_shape_simple_fn((p, l), || rng.normal());
+
+    // orthonormal basis Q (n × l) of the range of A
+    let mut q = orthonormalize(mul(&omega));
+    for _ in 0..POWER_ITERATIONS {
+        let z = orthonormalize(tmul(&q));
+        q = orthonormalize(mul(&z));
+    }
+
+    // B = Qᵀ A, so B Bᵀ = U Σ² Uᵀ and V = Bᵀ U Σ⁻¹
+    let bt = tmul(&q);
+    let (sigma2, u) = sorted_eigh(bt.t().dot(&bt))?;
+    if !sigma2.iter().all(|v| v.is_finite()) {
+        return Err(PcaTreeError::NonFinite("randomized SVD"));
+    }
+    let mut v = bt.dot(&u.slice(s![.., ..k]));
+    for (mut col, s2) in v.axis_iter_mut(Axis(1)).zip(sigma2.iter()) {
+        if *s2 > 0.0 {
+            col /= s2.sqrt();
+        }
+    }
+
+    Ok((orthonormalize(v), sigma2.slice(s![..k]).to_owned()))
+}
+
+/// Modified Gram–Schmidt on the columns, applied twice for f32 stability.
+/// Columns that are (numerically) dependent on earlier ones become 0.
+fn orthonormalize(mut a: Array2<f32>) -> Array2<f32> {
+    for _ in 0..2 {
+        for j in 0..a.ncols() {
+            for i in 0..j {
+                let proj = a.column(i).dot(&a.column(j));
+                let qi = a.column(i).to_owned();
+                a.column_mut(j).scaled_add(-proj, &qi);
+            }
+            let norm = a.column(j).dot(&a.column(j)).sqrt();
+            let mut col = a.column_mut(j);
+            if norm > 1e-6 {
+                col /= norm;
+            } else {
+                col.fill(0.0);
+            }
+        }
+    }
+    a
+}
@@ -1,4 +1,6 @@
+use crate::PcaTreeError;
 use ndarray::{Array2, ArrayView1};
+use serde::{Deserialize, Serialize};
 #[allow(dead_code, unused)] // creates a warning otherwise
 #[cfg(feature = "plot")]
 use plotters::prelude::*;
@@ -8,6 +10,42 @@ use std::fs::{File};
 use std::io::Write;
 use std::path::Path;
 
+/// Distance between two points in PCA space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Distance {
+    #[default]
+    Euclidean,
+    Manhattan,
+    /// 1 - cosine similarity. The zero vector (e.g. the germline, which sits at the
+    /// origin) has no direction: it is at distance 1 from every other point and 0 from itself.
+    Cosine,
+}
+
+impl Distance {
+    pub fn between(&self, a: ArrayView1<f32>, b: ArrayView1<f32>) -> f32 {
+        match self {
+            Distance::Euclidean => a.iter()
+                .zip(b.iter())
+                .map(|(x, y)| (x - y).powi(2))
+                .sum::<f32>()
+                .sqrt(),
+            Distance::Manhattan => a.iter()
+                .zip(b.iter())
+                .map(|(x, y)| (x - y).abs())
+                .sum::<f32>(),
+            Distance::Cosine => {
+                let dot = a.dot(&b);
+                let norm = a.dot(&a).sqrt() * b.dot(&b).sqrt();
+                match (a.dot(&a) == 0.0, b.dot(&b) == 0.0) {
+                    (true, true) => 0.0,
+                    (true, false) | (false, true) => 1.0,
+                    (false, false) => 1.0 - dot / norm,
+                }
+            }
+        }
+    }
+}
+
 pub struct MstTree {
     pub edges: Vec<(usize, usize, f32)>,
 }
@@ -18,6 +56,10 @@ impl MstTree {
         self.edges.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
     /// Write MST edges as TSV: parent<TAB>child<TAB>distance
     pub fn to_tsv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         self.to_delimited(path, '\t')
@@ -32,7 +74,61 @@ impl MstTree {
         let mut w = BufWriter::new(f);
 
         for (p, c, d) in &self.edges {
-            writeln!(w, "{}{}{}{}.{:.6}", p, sep, c, sep, d)?;
+            writeln!(w, "{}{}{}{}{:.6}", p, sep, c, sep, d)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write MST edges as TSV with a `parent child dist` header, nodes named by `ids`.
+    pub fn to_tsv_named<P: AsRef<Path>>(&self, path: P, ids: &[String]) -> std::io::Result<()> {
+        self.to_delimited_named(path, '\t', ids)
+    }
+
+    /// Like `to_delimited`, but nodes are written as sequence ids instead of row indices.
+    pub fn to_delimited_named<P: AsRef<Path>>(&self, path: P, sep: char, ids: &[String]) -> std::io::Result<()> {
+        self.write_named(path, sep, ids, None)
+    }
+
+    /// Like `to_tsv_named`, plus `parent_count` / `child_count` columns with node abundances.
+    pub fn to_tsv_counted<P: AsRef<Path>>(&self, path: P, ids: &[String], counts: &[usize]) -> std::io::Result<()> {
+        self.write_named(path, '\t', ids, Some(counts))
+    }
+
+    fn write_named<P: AsRef<Path>>(
+        &self,
+        path: P,
+        sep: char,
+        ids: &[String],
+        counts: Option<&[usize]>,
+    ) -> std::io::Result<()> {
+        let f = File::create(path)?;
+        let mut w = BufWriter::new(f);
+
+        write!(w, "parent{}child{}dist", sep, sep)?;
+        if counts.is_some() {
+            write!(w, "{}parent_count{}child_count", sep, sep)?;
+        }
+        writeln!(w)?;
+
+        for (p, c, d) in &self.edges {
+            let (Some(pid), Some(cid)) = (ids.get(*p), ids.get(*c)) else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("edge {p}-{c} has no matching id ({} ids)", ids.len()),
+                ));
+            };
+            write!(w, "{}{}{}{}{:.6}", pid, sep, cid, sep, d)?;
+            if let Some(counts) = counts {
+                let (Some(pc), Some(cc)) = (counts.get(*p), counts.get(*c)) else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("edge {p}-{c} has no matching count ({} counts)", counts.len()),
+                    ));
+                };
+                write!(w, "{}{}{}{}", sep, pc, sep, cc)?;
+            }
+            writeln!(w)?;
         }
 
         Ok(())
@@ -42,8 +138,23 @@ impl MstTree {
         self.edges.iter().map(|(_, _, d)| d).sum()
     }
 
-    pub fn build(coords: &Array2<f32>) -> Self {
+    /// Prim's MST over all points using Euclidean distances.
+    pub fn build(coords: &Array2<f32>) -> Result<Self, PcaTreeError> {
+        Self::build_with(coords, Distance::Euclidean)
+    }
+
+    /// Prim's MST over all points using the given distance.
+    ///
+    /// Zero or one point give an empty tree; identical points are joined by zero-length edges.
+    pub fn build_with(coords: &Array2<f32>, distance: Distance) -> Result<Self, PcaTreeError> {
         let n = coords.nrows();
+        if n < 2 {
+            return Ok(Self { edges: Vec::new() });
+        }
+        if !coords.iter().all(|v| v.is_finite()) {
+            return Err(PcaTreeError::NonFinite("PCA coordinates"));
+        }
+
         let mut in_tree = vec![false; n];
         let mut dist = vec![f32::INFINITY; n];
         let mut parent = vec![None; n];
@@ -51,7 +162,7 @@ impl MstTree {
         in_tree[0] = true;
 
         for i in 1..n {
-            dist[i] = Self::euclidean(coords.row(0), coords.row(i));
+            dist[i] = distance.between(coords.row(0), coords.row(i));
             parent[i] = Some(0);
         }
 
@@ -66,12 +177,15 @@ impl MstTree {
                 }
             }
 
-            let v = best.unwrap();
+            // all remaining distances are finite, so a closest node always exists
+            let Some(v) = best else {
+                return Err(PcaTreeError::NonFinite("tree distances"));
+            };
             in_tree[v] = true;
 
             for u in 0..n {
                 if !in_tree[u] {
-                    let d = Self::euclidean(coords.row(v), coords.row(u));
+                    let d = distance.between(coords.row(v), coords.row(u));
                     if d < dist[u] {
                         dist[u] = d;
                         parent[u] = Some(v);
@@ -82,12 +196,14 @@ impl MstTree {
 
         let mut edges = Vec::new();
         for i in 1..n {
-            edges.push((parent[i].unwrap(), i, dist[i]));
+            if let Some(p) = parent[i] {
+                edges.push((p, i, dist[i]));
+            }
         }
 
-        Self {
+        Ok(Self {
             edges,
-        }
+        })
     }
 
 
@@ -238,10 +354,14 @@ impl MstTree {
     }
     #[cfg(feature = "plot")]
     pub fn plot_2d(&self, coords: &ndarray::Array2<f32>, outfile: &str)
-        -> Result<(), Box<dyn std::error::Error>>
+        -> Result<(), PcaTreeError>
     {
         use plotters::prelude::*;
 
+        if coords.ncols() < 2 {
+            return Err(PcaTreeError::Plot("need at least 2 PCA components".to_string()));
+        }
+
         let root = BitMapBackend::new(outfile, (900, 900)).into_drawing_area();
         root.fill(&WHITE)?;
 
@@ -275,14 +395,6 @@ impl MstTree {
 
         Ok(())
     }
-
-    fn euclidean(a: ArrayView1<f32>, b: ArrayView1<f32>) -> f32 {
-        a.iter()
-            .zip(b.iter())
-            .map(|(x, y)| (x - y).powi(2))
-            .sum::<f32>()
-            .sqrt()
-    }
 }
 
 fn median(v: &[f32]) -> f32 {
@@ -33,3 +33,1063 @@ fn small_sequences_produce_tree() {
     assert!(seen.iter().all(|x| *x));
 }
 
+
+#[test]
+fn builder_is_lazy_until_fit() {
+    use pca_tree::{Distance, Encoding};
+
+    let seqs = vec![
+        "AAAA".to_string(),
+        "AAAT".to_string(),
+        "AATT".to_string(),
+        "TTTT".to_string(),
+    ];
+
+    let mut model = PcaTree::builder()
+        .encoding(Encoding::OneHot)
+        .components(2)
+        .distance(Distance::Manhattan)
+        .build(seqs);
+
+    assert!(model.coords().is_none());
+    assert!(model.tree().is_empty());
+
+    model.fit().unwrap();
+    assert_eq!(model.coords().unwrap().dim(), (4, 2));
+    assert_eq!(model.tree().len(), 3);
+}
+
+#[test]
+fn bad_input_is_reported_with_position() {
+    use pca_tree::{OneHotEncoder, PcaTreeError};
+
+    let enc = OneHotEncoder::new();
+
+    let err = enc.encode_batch(&["ACGT".to_string(), "ACXT".to_string()]).unwrap_err();
+    assert!(matches!(
+        err,
+        PcaTreeError::InvalidSymbol { index: 1, position: 2, symbol: 'X' }
+    ));
+
+    let err = enc.encode_relative(&["ACGT".to_string(), "ACG".to_string()]).unwrap_err();
+    assert!(matches!(
+        err,
+        PcaTreeError::LengthMismatch { index: 1, expected: 4, found: 3 }
+    ));
+
+    assert!(matches!(enc.encode_batch(&[]), Err(PcaTreeError::EmptyInput)));
+}
+
+#[test]
+fn tiny_and_degenerate_inputs() {
+    use pca_tree::PcaTreeError;
+
+    let fit = |seqs: &[&str], k: usize| {
+        PcaTree::builder()
+            .components(k)
+            .fit(seqs.iter().map(|s| s.to_string()).collect())
+    };
+
+    assert!(matches!(fit(&[], 2), Err(PcaTreeError::EmptyInput)));
+
+    // one sequence: trivial coordinates, no edges
+    let one = fit(&["ACGT"], 2).unwrap();
+    assert_eq!(one.coords().unwrap().dim(), (1, 2));
+    assert!(one.coords().unwrap().iter().all(|v| *v == 0.0));
+    assert!(one.tree().is_empty());
+
+    // two sequences: a single edge
+    let two = fit(&["ACGT", "ACGA"], 2).unwrap();
+    assert_eq!(two.tree().len(), 1);
+
+    // identical sequences: zero coordinates, zero-length edges
+    let same = fit(&["ACGT", "ACGT", "ACGT"], 2).unwrap();
+    assert!(same.coords().unwrap().iter().all(|v| v.abs() < 1e-6));
+    assert!(same.tree().iter().all(|(_, _, d)| *d < 1e-6));
+
+    // k larger than the 4 relative features is clamped
+    let wide = fit(&["ACGT", "ACGA", "TCGA"], 10).unwrap();
+    assert_eq!(wide.coords().unwrap().ncols(), 4);
+    assert_eq!(wide.pca.n_components(), 4);
+}
+
+#[test]
+fn one_hot_handles_iupac_and_gaps() {
+    use pca_tree::OneHotEncoder;
+
+    let x = OneHotEncoder::new()
+        .encode_batch(&["ANR-".to_string(), "acgt".to_string()])
+        .unwrap();
+
+    // 5 channels (A, C, G, T, gap) per position
+    assert_eq!(x.dim(), (2, 20));
+    assert_eq!(x.row(0).to_vec()[0..5], [1.0, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(x.row(0).to_vec()[5..10], [0.25, 0.25, 0.25, 0.25, 0.0]);
+    assert_eq!(x.row(0).to_vec()[10..15], [0.5, 0.0, 0.5, 0.0, 0.0]);
+    assert_eq!(x.row(0).to_vec()[15..20], [0.0, 0.0, 0.0, 0.0, 1.0]);
+
+    // every position sums to one
+    assert!(x.row(1).sum() == 4.0);
+}
+
+#[test]
+fn amino_acid_encodings() {
+    use pca_tree::{AminoAcidEncoder, Encoding};
+
+    let seqs = vec![
+        "CARDY".to_string(),
+        "CARDW".to_string(),
+        "CAR-X".to_string(),
+    ];
+
+    let enc = AminoAcidEncoder::new();
+    let x = enc.encode_batch(&seqs).unwrap();
+    assert_eq!(x.dim(), (3, 5 * 23));
+    // C is the 2nd amino acid column, gap is channel 20
+    assert_eq!(x[[0, 1]], 1.0);
+    assert_eq!(x[[2, 3 * 23 + 20]], 1.0);
+
+    let rel = enc.encode_relative(&seqs).unwrap();
+    assert_eq!(rel.row(2).to_vec(), [0.0, 0.0, 0.0, -1.0, 0.5]);
+
+    let model = PcaTree::builder()
+        .encoding(Encoding::AminoAcid)
+        .components(2)
+        .fit(seqs)
+        .unwrap();
+    assert_eq!(model.tree().len(), 2);
+}
+
+#[test]
+fn property_encoding_reflects_substitution_similarity() {
+    use pca_tree::{AaProperties, PropertyEncoder};
+
+    let seqs = vec!["I".to_string(), "L".to_string(), "D".to_string(), "-".to_string()];
+
+    for props in [AaProperties::Atchley, AaProperties::Kidera, AaProperties::Blosum62] {
+        let x = PropertyEncoder::new(props).encode_batch(&seqs).unwrap();
+        assert_eq!(x.ncols(), props.dim() + 1);
+
+        let dist = |a: usize, b: usize| (&x.row(a) - &x.row(b)).mapv(|v| v * v).sum();
+        // I and L are closer than I and D
+        assert!(dist(0, 1) < dist(0, 2), "{props:?}");
+        // gap flag
+        assert_eq!(x[[3, props.dim()]], 1.0);
+    }
+}
+
+#[test]
+fn custom_encoder_plugs_into_builder() {
+    use ndarray::Array2;
+    use pca_tree::{PcaTreeError, SequenceEncoder};
+
+    /// GC content per sequence, a single feature.
+    struct GcContent;
+
+    impl SequenceEncoder for GcContent {
+        fn encode(&self, sequences: &[String]) -> Result<Array2<f32>, PcaTreeError> {
+            Ok(Array2::from_shape_fn((sequences.len(), 1), |(i, _)| {
+                let s = &sequences[i];
+                s.chars().filter(|c| *c == 'G' || *c == 'C').count() as f32 / s.len() as f32
+            }))
+        }
+
+        fn feature_names(&self, _len: usize) -> Vec<String> {
+            vec!["gc".to_string()]
+        }
+    }
+
+    let model = PcaTree::builder()
+        .encoder(GcContent)
+        .components(1)
+        .fit(vec!["AAAA".to_string(), "GCAA".to_string(), "GCGC".to_string()])
+        .unwrap();
+
+    assert_eq!(model.features, ["gc"]);
+    assert_eq!(model.coords().unwrap().ncols(), 1);
+    assert_eq!(model.tree().len(), 2);
+
+    let one_hot = pca_tree::Encoding::OneHot.feature_names(2);
+    assert_eq!(one_hot[..6], ["1_A", "1_C", "1_G", "1_T", "1_gap", "2_A"]);
+}
+
+#[test]
+fn variable_length_sequences_are_aligned() {
+    use pca_tree::Aligner;
+
+    let seqs = vec![
+        "ACGTACGTAC".to_string(),
+        "ACGTAGTAC".to_string(),
+        "ACGTTACGTAC".to_string(),
+        "ACGTACGTAC".to_string(),
+    ];
+
+    let aln = Aligner::new().align(&seqs).unwrap();
+    let len = aln[0].len();
+    assert!(aln.iter().all(|s| s.len() == len));
+    // removing the gaps gives back the input
+    for (a, s) in aln.iter().zip(&seqs) {
+        assert_eq!(&a.replace('-', ""), s);
+    }
+
+    let (r, s) = Aligner::new().pairwise(b"ACGTACGT", b"ACGACGT");
+    assert_eq!(r, b"ACGTACGT");
+    assert_eq!(s, b"ACG-ACGT");
+
+    let model = PcaTree::builder()
+        .align(Aligner::new().with_reference("ACGTACGTAC"))
+        .components(2)
+        .fit(seqs)
+        .unwrap();
+    assert_eq!(model.alignment.as_ref().unwrap().len(), 4);
+    assert_eq!(model.tree().len(), 3);
+}
+
+#[test]
+fn germline_is_origin_and_root() {
+    use pca_tree::{OneHotEncoder, PcaTreeError, GERMLINE_ID};
+
+    let seqs = vec![
+        "ACGTACGT".to_string(),
+        "ACGAACGT".to_string(),
+        "TCGAACGT".to_string(),
+        "TCGAACGA".to_string(),
+    ];
+
+    let x = OneHotEncoder::new().encode_relative_to(&seqs, "ACGTACGT").unwrap();
+    assert!(x.row(0).iter().all(|v| *v == 0.0));
+    assert_eq!(x.row(3).sum(), 3.0);
+    assert!(matches!(
+        OneHotEncoder::new().encode_relative_to(&seqs, "ACGT"),
+        Err(PcaTreeError::ReferenceLength { expected: 8, found: 4 })
+    ));
+
+    let model = PcaTree::builder()
+        .germline("ACGTACGT")
+        .components(2)
+        .fit(seqs)
+        .unwrap();
+
+    assert_eq!(model.ids()[0], GERMLINE_ID);
+    assert!(model.coords().unwrap().row(0).iter().all(|v| v.abs() < 1e-6));
+    // the germline only ever shows up as a parent
+    assert!(model.tree().iter().all(|(_, c, _)| *c != 0));
+    assert_eq!(model.tree().len(), 4);
+}
+
+#[test]
+fn cosine_distance_keeps_germline_tree() {
+    use pca_tree::Distance;
+
+    // two lineages accumulating mutations away from the germline
+    let seqs: Vec<String> = ["TAAAAAAA", "TTAAAAAA", "TTTAAAAA", "AAAAAGAA", "AAAAAGGA", "AAAAAGGG"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let model = PcaTree::builder()
+        .germline("AAAAAAAA")
+        .components(2)
+        .distance(Distance::Cosine)
+        .fit(seqs)
+        .unwrap();
+
+    // the germline at the origin is not at distance 0 from everything
+    let from_root: Vec<_> = model.tree().iter().filter(|(p, _, _)| *p == 0).collect();
+    assert!(from_root.len() < 6, "star around the germline: {:?}", model.tree());
+    assert!(from_root.iter().all(|(_, _, d)| (*d - 1.0).abs() < 1e-6));
+
+    let zero = ndarray::Array1::<f32>::zeros(2);
+    let one = ndarray::arr1(&[1.0f32, 0.0]);
+    assert_eq!(Distance::Cosine.between(zero.view(), zero.view()), 0.0);
+    assert_eq!(Distance::Cosine.between(zero.view(), one.view()), 1.0);
+}
+
+#[test]
+fn substitution_encoding_keeps_mutation_kind() {
+    use pca_tree::{MutationDetail, SequenceEncoder, SubstitutionEncoder};
+
+    // reference codons: ATG (M), CTG (L)
+    let seqs = vec![
+        "ATGCTG".to_string(),
+        "GTGCTA".to_string(), // A>G transition, non-synonymous (V); G>A synonymous (L)
+        "CTGTTG".to_string(), // A>C transversion, non-synonymous (L); C>T synonymous (L)
+        "RTG-TG".to_string(), // half A, half G at the first position; gap
+    ];
+
+    let base = SubstitutionEncoder::new(MutationDetail::Base).with_reference("ATGCTG");
+    let x = base.encode(&seqs).unwrap();
+    assert_eq!(x.ncols(), 30);
+    assert!(x.row(0).iter().all(|v| *v == 0.0));
+    assert_eq!(x[[1, 2]], 1.0); // pos 1 -> G
+    assert_eq!(x[[2, 1]], 1.0); // pos 1 -> C
+    assert_eq!(x[[3, 2]], 0.5);
+    assert_eq!(x[[3, 3 * 5 + 4]], 1.0);
+    assert_eq!(base.feature_names(6)[2], "1_G");
+
+    let tstv = SubstitutionEncoder::new(MutationDetail::TransitionTransversion)
+        .with_reference("ATGCTG");
+    let x = tstv.encode(&seqs).unwrap();
+    assert_eq!((x[[1, 0]], x[[1, 1]]), (1.0, 0.0));
+    assert_eq!((x[[2, 0]], x[[2, 1]]), (0.0, 1.0));
+
+    let codon = SubstitutionEncoder::new(MutationDetail::Codon { frame: 0 })
+        .with_reference("ATGCTG");
+    let x = codon.encode(&seqs).unwrap();
+    assert_eq!((x[[1, 0]], x[[1, 1]]), (0.0, 1.0));
+    assert_eq!((x[[1, 15]], x[[1, 16]]), (1.0, 0.0));
+    assert_eq!((x[[2, 9]], x[[2, 10]]), (1.0, 0.0));
+
+    // without a reference the consensus is used
+    let model = PcaTree::builder()
+        .encoder(SubstitutionEncoder::new(MutationDetail::TransitionTransversion))
+        .components(2)
+        .fit(seqs)
+        .unwrap();
+    assert_eq!(model.features.len(), 18);
+}
+
+#[test]
+fn codon_encoder_uses_reading_frame() {
+    use pca_tree::{CodonEncoder, CodonFeatures, SequenceEncoder};
+
+    // frame 1: codons ATG CTG, the leading C and trailing A are dropped
+    let seqs = vec![
+        "CATGCTGA".to_string(),
+        "CATGCTAA".to_string(), // CTG -> CTA: silent (L)
+        "CGTGCTGA".to_string(), // ATG -> GTG: replacement (M -> V)
+        "C---CTGA".to_string(),
+    ];
+
+    let aa = CodonEncoder::new(1, CodonFeatures::Residue);
+    assert_eq!(aa.translate(&seqs[2]), "VL");
+    assert_eq!(aa.translate(&seqs[3]), "-L");
+    let x = aa.encode(&seqs).unwrap();
+    assert_eq!(x.dim(), (4, 2 * 23));
+    assert_eq!(aa.feature_names(8).len(), 46);
+
+    let codon = CodonEncoder::new(1, CodonFeatures::Codon);
+    let x = codon.encode(&seqs).unwrap();
+    assert_eq!(x.ncols(), 2 * 65);
+    assert_eq!(codon.feature_names(8)[0], "1_TTT");
+    assert_eq!(x.row(0).sum(), 2.0);
+    assert_eq!(x[[3, 64]], 1.0);
+
+    let rs = CodonEncoder::new(1, CodonFeatures::Mutations).with_reference("CATGCTGA");
+    let x = rs.encode(&seqs).unwrap();
+    assert!(x.row(0).iter().all(|v| *v == 0.0));
+    assert_eq!(x.row(1).to_vec(), vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    assert_eq!(x.row(2).to_vec(), vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(x.row(3).to_vec(), vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+
+    // the germline replaces the consensus when set on the builder
+    let model = PcaTree::builder()
+        .encoder(CodonEncoder::new(1, CodonFeatures::Mutations))
+        .germline("CATGCTGA")
+        .components(2)
+        .fit(seqs.clone())
+        .unwrap();
+    assert_eq!(model.features, vec!["1_R", "1_S", "1_gap", "2_R", "2_S", "2_gap"]);
+
+    // only frames 0 to 2 exist
+    use pca_tree::{MutationDetail, PcaTreeError, SubstitutionEncoder};
+    let shifted = CodonEncoder::new(3, CodonFeatures::Codon).encode(&seqs);
+    assert!(matches!(shifted, Err(PcaTreeError::ReadingFrame(3))));
+    let syn = SubstitutionEncoder::new(MutationDetail::Codon { frame: 4 }).encode(&seqs);
+    assert!(matches!(syn, Err(PcaTreeError::ReadingFrame(4))));
+}
+
+#[test]
+fn kmer_spectrum_of_unaligned_sequences() {
+    use pca_tree::{KmerEncoder, SequenceEncoder};
+
+    let seqs = vec![
+        "ACGTAC".to_string(),
+        "AC-GTACGT".to_string(),
+        "TTTT".to_string(),
+        "ACNGT".to_string(),
+    ];
+
+    let enc = KmerEncoder::new(2).unwrap().normalized(false);
+    let x = enc.encode(&seqs).unwrap();
+    assert_eq!(x.dim(), (4, 16));
+    let names = enc.feature_names(0);
+    let col = |k: &str| names.iter().position(|n| n == k).unwrap();
+    assert_eq!(x[[0, col("AC")]], 2.0);
+    assert_eq!(x.row(0).sum(), 5.0);
+    // gaps are removed before counting
+    assert_eq!(x[[1, col("CG")]], 2.0);
+    assert_eq!(x[[2, col("TT")]], 3.0);
+    // N breaks k-mers
+    assert_eq!(x.row(3).sum(), 2.0);
+
+    // AC and its reverse complement GT fall together
+    let canon = KmerEncoder::new(2).unwrap().canonical(true).encode(&seqs).unwrap();
+    assert_eq!(canon.row(0).sum(), 1.0);
+    assert_eq!(canon[[0, col("AC")]], 3.0 / 5.0);
+    assert_eq!(canon[[0, col("GT")]], 0.0);
+
+    let hashed = KmerEncoder::new(5).unwrap().hashed(8);
+    assert_eq!(hashed.encode(&seqs).unwrap().dim(), (4, 8));
+    assert_eq!(hashed.feature_names(0)[7], "h7");
+
+    // sizes are checked instead of panicking or allocating 4^k columns
+    use pca_tree::PcaTreeError;
+    assert!(matches!(KmerEncoder::new(0), Err(PcaTreeError::KmerSize { k: 0, .. })));
+    assert!(matches!(KmerEncoder::new(32), Err(PcaTreeError::KmerSize { k: 32, .. })));
+    let large = KmerEncoder::new(12).unwrap();
+    assert!(matches!(large.encode(&seqs), Err(PcaTreeError::KmerSize { k: 12, hashed: false })));
+    assert!(large.feature_names(0).is_empty());
+    assert_eq!(large.hashed(64).encode(&seqs).unwrap().dim(), (4, 64));
+
+    let model = PcaTree::builder()
+        .encoder(KmerEncoder::new(2).unwrap())
+        .components(2)
+        .fit(seqs)
+        .unwrap();
+    assert_eq!(model.tree().len(), 3);
+}
+
+#[test]
+fn position_mask_selects_columns() {
+    use pca_tree::{Alphabet, ImgtRegion, OneHotEncoder, PositionMask};
+
+    let mask = PositionMask::regions(&[(2, 3), (6, 6)]);
+    assert_eq!(mask.positions(8), vec![1, 2, 5]);
+    assert_eq!(mask.clone().invert().positions(8), vec![0, 3, 4, 6, 7]);
+    assert_eq!(PositionMask::from_bools(&[true, false, true]).positions(8), vec![0, 2]);
+    assert_eq!(PositionMask::parse("2-3, 6", Alphabet::Nucleotide).unwrap(), mask);
+    assert!(PositionMask::parse("3-2", Alphabet::Nucleotide).is_err());
+
+    let cdr1 = PositionMask::imgt(&[ImgtRegion::Cdr1], Alphabet::Nucleotide).positions(400);
+    assert_eq!((cdr1[0], cdr1.len()), (78, 36));
+    let cdr3 = PositionMask::parse("cdr3", Alphabet::AminoAcid).unwrap().positions(120);
+    assert_eq!((cdr3[0], cdr3.len()), (104, 16));
+
+    // only the masked columns differ, so the unmasked ones carry no variance
+    let seqs = vec![
+        "ACGTACGT".to_string(),
+        "TCGTACGA".to_string(),
+        "ACCTACGT".to_string(),
+        "ACCTAGGT".to_string(),
+    ];
+    let model = PcaTree::builder()
+        .encoder(OneHotEncoder::new())
+        .mask(PositionMask::regions(&[(3, 3), (6, 6)]))
+        .components(2)
+        .fit(seqs)
+        .unwrap();
+
+    assert_eq!(model.positions.as_deref(), Some(&[2, 5][..]));
+    assert_eq!(model.features.len(), 10);
+    assert_eq!(model.features[0], "3_A");
+    assert_eq!(model.features[5], "6_A");
+    assert!(model.mask().is_some());
+    // sequences 0 and 1 only differ outside the mask
+    let c = model.coords().unwrap();
+    assert!((&c.row(0) - &c.row(1)).iter().all(|v| v.abs() < 1e-5));
+}
+
+#[test]
+fn low_quality_bases_are_down_weighted() {
+    use pca_tree::{QualityWeighting, SeqRecord};
+
+    let record = |id: &str, seq: &str, qual: &str| SeqRecord {
+        id: id.to_string(),
+        seq: seq.to_string(),
+        qual: Some(qual.to_string()),
+    };
+    // s1 differs from s0 only by a Q2 call ('#') at position 4
+    let records = vec![
+        record("s0", "ACGTACGT", "IIIIIIII"),
+        record("s1", "ACGAACGT", "III#IIII"),
+        record("s2", "TCGTACGA", "IIIIIIII"),
+        record("s3", "TCGTACGA", "IIIIIIII"),
+    ];
+
+    assert_eq!(QualityWeighting::Threshold(20).weight(19), 0.0);
+    assert!((QualityWeighting::Probabilistic.weight(20) - 0.99).abs() < 1e-6);
+
+    let plain = PcaTree::builder().components(2).fit_records(records.clone()).unwrap();
+    let masked = PcaTree::builder()
+        .quality(QualityWeighting::Threshold(20))
+        .components(2)
+        .fit_records(records.clone())
+        .unwrap();
+
+    let dist = |m: &PcaTree| {
+        let c = m.coords().unwrap();
+        (&c.row(0) - &c.row(1)).mapv(|v| v * v).sum().sqrt()
+    };
+    assert!(dist(&plain) > 0.1);
+    assert!(dist(&masked) < 1e-5);
+
+    // confident calls are not blended at all, so the sparse fit keeps only mutations
+    let confident: Vec<SeqRecord> =
+        records.iter().map(|r| record(&r.id, &r.seq, &"I".repeat(r.seq.len()))).collect();
+    let fit = |quality: Option<QualityWeighting>| {
+        let builder = PcaTree::builder().encoding(pca_tree::Encoding::OneHot).sparse(true).components(2);
+        let builder = match quality {
+            Some(q) => builder.quality(q),
+            None => builder,
+        };
+        builder.fit_records(confident.clone()).unwrap()
+    };
+    assert_eq!(fit(Some(QualityWeighting::Probabilistic)).coords(), fit(None).coords());
+
+    // weights follow the bases through the alignment
+    let mut gapped = records;
+    gapped[1] = record("s1", "ACGAACG", "III#III");
+    let aligned = PcaTree::builder()
+        .align(pca_tree::Aligner::new())
+        .quality(QualityWeighting::Threshold(20))
+        .components(2)
+        .fit_records(gapped)
+        .unwrap();
+    assert_eq!(aligned.alignment.as_ref().unwrap()[1], "ACGAACG-");
+}
+
+#[test]
+fn masked_bases_count_as_consensus_in_one_hot() {
+    use pca_tree::{Encoding, QualityWeighting, SeqRecord};
+
+    let record = |seq: &str, qual: &str| SeqRecord {
+        id: seq.to_string(),
+        seq: seq.to_string(),
+        qual: Some(qual.to_string()),
+    };
+    // s1 has a Q2 mismatch at position 4; the consensus there is T
+    let noisy = vec![
+        record("ACGTACGT", "IIIIIIII"),
+        record("ACGAACGT", "III#IIII"),
+        record("TCGTACGA", "IIIIIIII"),
+        record("TCGTACGC", "IIIIIIII"),
+    ];
+    let mut clean = noisy.clone();
+    clean[1] = record("ACGTACGT", "IIIIIIII");
+
+    for sparse in [false, true] {
+        let fit = |records: Vec<SeqRecord>, quality: bool| {
+            let mut b = PcaTree::builder().encoding(Encoding::OneHot).components(3).sparse(sparse);
+            if quality {
+                b = b.quality(QualityWeighting::Threshold(20));
+            }
+            b.fit_records(records).unwrap()
+        };
+        let distances = |m: &PcaTree| {
+            let c = m.coords().unwrap().clone();
+            let mut d = Vec::new();
+            for i in 0..c.nrows() {
+                for j in i + 1..c.nrows() {
+                    d.push((&c.row(i) - &c.row(j)).mapv(|v| v * v).sum().sqrt());
+                }
+            }
+            d
+        };
+
+        // masking the mismatch is the same as calling the consensus base
+        let masked = distances(&fit(noisy.clone(), true));
+        let expected = distances(&fit(clean.clone(), false));
+        for (m, e) in masked.iter().zip(&expected) {
+            assert!((m - e).abs() < 1e-4, "sparse {sparse}: {masked:?} vs {expected:?}");
+        }
+        // not an all-zero pseudo-symbol away from every base
+        assert!(masked[0] < 1e-4);
+        assert!(distances(&fit(noisy.clone(), false))[0] > 0.1);
+    }
+}
+
+#[test]
+fn duplicates_collapse_into_weighted_nodes() {
+    let seqs: Vec<String> = ["ACGTACGT", "ACGTACGT", "TCGTACGA", "ACGTACGT", "TCGAACGA", "TCGTACGA"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let model = PcaTree::builder()
+        .collapse_duplicates(true)
+        .components(2)
+        .fit(seqs.clone())
+        .unwrap();
+
+    assert_eq!(model.ids(), &["0", "2", "4"]);
+    assert_eq!(model.counts, vec![3, 2, 1]);
+    assert_eq!(model.members.as_ref().unwrap()[0], vec!["0", "1", "3"]);
+    assert_eq!(model.tree().len(), 2);
+    assert!(model.tree().iter().all(|(_, _, d)| *d > 0.0));
+
+    // the weighted PCA of the unique rows spans the same axes as the full PCA
+    let full = PcaTree::builder().components(2).fit(seqs).unwrap();
+    let (a, b) = (model.coords().unwrap(), full.coords().unwrap());
+    for (u, i) in [(0, 0), (1, 2), (2, 4)] {
+        for j in 0..2 {
+            assert!((a[[u, j]].abs() - b[[i, j]].abs()).abs() < 1e-4);
+        }
+    }
+
+    let dir = std::env::temp_dir().join("pca_tree_collapse_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    model.pca.to_tsv_counted(dir.join("pca.tsv"), model.ids(), &model.counts).unwrap();
+    model.tree.to_tsv_counted(dir.join("tree.tsv"), model.ids(), &model.counts).unwrap();
+    model.members_to_tsv(dir.join("members.tsv")).unwrap();
+
+    let pca = std::fs::read_to_string(dir.join("pca.tsv")).unwrap();
+    assert!(pca.starts_with("id\tcount\tPC1\tPC2\n0\t3\t"));
+    let tree = std::fs::read_to_string(dir.join("tree.tsv")).unwrap();
+    assert!(tree.starts_with("parent\tchild\tdist\tparent_count\tchild_count\n"));
+    let members = std::fs::read_to_string(dir.join("members.tsv")).unwrap();
+    assert!(members.contains("0\t3\t0,1,3\n"));
+}
+
+#[test]
+fn sparse_path_matches_dense() {
+    use pca_tree::{CsrMatrix, Encoding, OneHotEncoder, PcaModel, SequenceEncoder};
+
+    let seqs: Vec<String> = ["ACGTACGTAA", "ACGAACGTAA", "TCGAACGTCA", "TCGAACGACA", "ACNTACGTAA"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let dense = OneHotEncoder::new().encode(&seqs).unwrap();
+    let sparse = OneHotEncoder::new().encode_sparse(&seqs).unwrap();
+    assert_eq!(sparse.to_dense(), dense);
+    assert_eq!(sparse.nnz(), 5 * 10 + 3);
+    assert_eq!(CsrMatrix::from_dense(&dense), sparse);
+
+    let mut a = PcaModel::new(2);
+    a.fit_transform(&dense).unwrap();
+    let mut b = PcaModel::new(2);
+    b.fit_transform_sparse(&sparse).unwrap();
+    for (x, y) in a.coords().iter().zip(b.coords().iter()) {
+        assert!((x - y).abs() < 1e-4);
+    }
+
+    // through the builder, with a germline and collapsed duplicates
+    let fit = |sparse: bool| {
+        PcaTree::builder()
+            .encoding(Encoding::OneHot)
+            .germline("ACGTACGTAA")
+            .collapse_duplicates(true)
+            .sparse(sparse)
+            .components(2)
+            .fit(seqs.clone())
+            .unwrap()
+    };
+    let (d, s) = (fit(false), fit(true));
+    assert!(s.coords().unwrap().row(0).iter().all(|v| v.abs() < 1e-6));
+    for (x, y) in d.coords().unwrap().iter().zip(s.coords().unwrap().iter()) {
+        assert!((x - y).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn relative_encodings_stay_sparse() {
+    use pca_tree::{
+        Alphabet, CsrMatrix, MutationDetail, PcaModel, RelativeEncoder, SequenceEncoder, SubstitutionEncoder,
+    };
+
+    let seqs: Vec<String> = ["ACGTACGTAA", "ACGAACGTAA", "TCGAACGTCA", "TCGAACGACA", "ACGTACGT-A"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    // only mutations and gaps are stored
+    let relative = RelativeEncoder::new(Alphabet::Nucleotide);
+    let sparse = relative.encode_sparse(&seqs).unwrap();
+    assert_eq!(sparse.to_dense(), relative.encode(&seqs).unwrap());
+    assert_eq!(sparse.nnz(), 8);
+    let frozen = relative.freeze(&seqs).unwrap().unwrap();
+    assert_eq!(frozen.encode_sparse(&seqs).unwrap(), sparse);
+    for detail in [MutationDetail::Base, MutationDetail::TransitionTransversion, MutationDetail::Codon { frame: 0 }] {
+        let enc = SubstitutionEncoder::new(detail);
+        let sparse = enc.encode_sparse(&seqs).unwrap();
+        assert_eq!(sparse.to_dense(), enc.encode(&seqs).unwrap());
+        assert_eq!(sparse.nnz(), 8);
+    }
+
+    // fewer rows than features: the row Gram matrix gives the full spectrum
+    let x = pca_tree::OneHotEncoder::new().encode(&seqs).unwrap();
+    let mut dense = PcaModel::new(3);
+    dense.fit_transform(&x).unwrap();
+    let mut sparse = PcaModel::new(3);
+    sparse.fit_transform_sparse(&CsrMatrix::from_dense(&x)).unwrap();
+    assert_eq!(sparse.eigenvalues.len(), x.ncols());
+    for (a, b) in dense.eigenvalues.iter().zip(sparse.eigenvalues.iter()) {
+        assert!((a - b).abs() < 1e-4, "{} vs {}", dense.eigenvalues, sparse.eigenvalues);
+    }
+    // the n < p branch decomposes the row Gram matrix, yet the signs agree
+    for (a, b) in dense.coords().iter().zip(sparse.coords().iter()) {
+        assert!((a - b).abs() < 1e-4);
+    }
+    let new = sparse.transform(&x).unwrap();
+    for (a, b) in new.iter().zip(sparse.coords().iter()) {
+        assert!((a - b).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn column_stats_report_consensus_and_variation() {
+    use pca_tree::{Alphabet, ColumnStats, OneHotEncoder};
+
+    let seqs: Vec<String> = ["ACGT", "ACGA", "AC-A", "TCGu"].iter().map(|s| s.to_string()).collect();
+
+    let stats = OneHotEncoder::new().column_stats(&seqs).unwrap();
+    assert_eq!(stats.len(), 4);
+    assert_eq!(stats.counts[0], vec![3, 0, 0, 1]);
+    assert_eq!(stats.counts[3], vec![2, 0, 0, 2]);
+    assert_eq!(stats.gaps[2], 1);
+    assert_eq!(stats.entropy(1), 0.0);
+    assert_eq!(stats.entropy(3), 1.0);
+    assert_eq!(stats.gap_fraction(2), 0.25);
+    assert_eq!(&stats.consensus()[..3], "ACG");
+
+    let model = PcaTree::builder().components(2).fit(seqs).unwrap();
+    assert_eq!(model.column_stats(Alphabet::Nucleotide).unwrap(), stats);
+
+    let dir = std::env::temp_dir().join("pca_tree_column_stats_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    stats.to_tsv(dir.join("stats.tsv")).unwrap();
+    stats.to_fasta(dir.join("consensus.fa"), "clone1").unwrap();
+
+    let tsv = std::fs::read_to_string(dir.join("stats.tsv")).unwrap();
+    let mut lines = tsv.lines();
+    assert_eq!(lines.next(), Some("pos\tconsensus\tA\tC\tG\tT\tgap\tother\tentropy\tgap_fraction"));
+    assert_eq!(lines.next(), Some("1\tA\t3\t0\t0\t1\t0\t0\t0.811278\t0.000000"));
+    let fa = std::fs::read_to_string(dir.join("consensus.fa")).unwrap();
+    assert!(fa.starts_with(">clone1\nACG"));
+
+    let aa = ColumnStats::new(&["MK*".to_string(), "MR-".to_string()], Alphabet::AminoAcid).unwrap();
+    assert_eq!((aa.other[2], aa.gaps[2]), (1, 1));
+}
+
+#[test]
+fn rna_input_matches_dna() {
+    use pca_tree::{
+        Alphabet, CodonEncoder, CodonFeatures, MutationDetail, OneHotEncoder, RelativeEncoder, SequenceEncoder,
+        SubstitutionEncoder,
+    };
+
+    let dna: Vec<String> = ["ATGCTGTTT", "ATGCTATTT", "GTGCTGTTC", "ATGCTGTTT"].iter().map(|s| s.to_string()).collect();
+    let rna: Vec<String> = dna.iter().map(|s| s.replace('T', "U").to_lowercase()).collect();
+
+    let encoders: Vec<Box<dyn SequenceEncoder>> = vec![
+        Box::new(RelativeEncoder::new(Alphabet::Nucleotide)),
+        Box::new(SubstitutionEncoder::new(MutationDetail::Base)),
+        Box::new(SubstitutionEncoder::new(MutationDetail::Codon { frame: 0 })),
+        Box::new(CodonEncoder::new(0, CodonFeatures::Mutations)),
+    ];
+    for enc in &encoders {
+        let x = enc.encode(&rna).unwrap();
+        assert_eq!(x, enc.encode(&dna).unwrap());
+        // U positions matching the consensus are not mutations
+        assert_eq!(x.row(0).sum(), 0.0);
+    }
+
+    // an RNA germline against DNA reads, and the other way round
+    let germline = "AUGCUGUUU";
+    assert_eq!(
+        OneHotEncoder::new().encode_relative_to(&dna, germline).unwrap(),
+        OneHotEncoder::new().encode_relative_to(&rna, &dna[0]).unwrap()
+    );
+    let relative = RelativeEncoder::new(Alphabet::Nucleotide).with_reference(germline);
+    assert_eq!(relative.encode(&dna).unwrap().row(0).sum(), 0.0);
+}
+
+#[test]
+fn relative_encoding_weighs_ambiguity_codes() {
+    use pca_tree::{OneHotEncoder, PcaTreeError};
+
+    let seqs: Vec<String> = ["ACGTA", "NRGTA", "RCNT-"].iter().map(|s| s.to_string()).collect();
+    let x = OneHotEncoder::new().encode_relative_to(&seqs, "ARGTA").unwrap();
+    // N against A is 3/4 off, R against A half, anything within the reference's R is 0
+    assert_eq!(x.row(0).to_vec(), vec![0.0, 1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(x.row(1).to_vec(), vec![0.75, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(x.row(2).to_vec(), vec![0.5, 1.0, 0.75, 0.0, -1.0]);
+
+    let typo: Vec<String> = ["ACGTA", "ACZTA"].iter().map(|s| s.to_string()).collect();
+    assert!(matches!(
+        OneHotEncoder::new().encode_relative(&typo),
+        Err(PcaTreeError::InvalidSymbol { index: 1, position: 2, symbol: 'Z' })
+    ));
+}
+
+#[test]
+fn consensus_rules_are_deterministic() {
+    use pca_tree::{
+        Alphabet, ColumnStats, ConsensusRules, GapColumns, RelativeEncoder, SequenceEncoder,
+        TieBreak,
+    };
+
+    // col 1: A/G tie, col 2: gap majority, col 3: all gaps, col 4: C 2 of 4
+    let seqs: Vec<String> = ["AA-C", "G--C", "G--T", "A--G"].iter().map(|s| s.to_string()).collect();
+    let stats = ColumnStats::new(&seqs, Alphabet::Nucleotide).unwrap();
+
+    assert_eq!(stats.consensus(), "AANC");
+
+    let rules = ConsensusRules {
+        ties: TieBreak::Ambiguity,
+        min_support: 0.5,
+        gap_columns: GapColumns::Flag,
+    };
+    assert_eq!(stats.clone().with_rules(rules).consensus(), "R--C");
+    let strict = ConsensusRules { min_support: 0.6, ..rules };
+    assert_eq!(stats.clone().with_rules(strict).consensus(), "N--N");
+
+    // the same input in another order gives the same consensus
+    let mut reversed = seqs.clone();
+    reversed.reverse();
+    assert_eq!(ColumnStats::new(&reversed, Alphabet::Nucleotide).unwrap().consensus(), "AANC");
+
+    let drop = ConsensusRules { gap_columns: GapColumns::Drop, ..Default::default() };
+    let x = RelativeEncoder::new(Alphabet::Nucleotide)
+        .with_consensus(drop)
+        .encode(&seqs)
+        .unwrap();
+    assert!(x.column(1).iter().all(|v| *v == 0.0));
+    assert!(x.column(2).iter().all(|v| *v == 0.0));
+    assert_eq!(x.column(0).to_vec(), vec![0.0, 1.0, 1.0, 0.0]);
+
+    // the rules reach the substitution and codon encodings, also once frozen
+    use pca_tree::{CodonEncoder, CodonFeatures, MutationDetail, SubstitutionEncoder};
+    let sub = SubstitutionEncoder::new(MutationDetail::Base).with_consensus(drop);
+    let x = sub.encode(&seqs).unwrap();
+    assert!(x.slice(ndarray::s![.., 5..15]).iter().all(|v| *v == 0.0));
+    assert_eq!(sub.freeze(&seqs).unwrap().unwrap().encode(&seqs).unwrap(), x);
+    let default = SubstitutionEncoder::new(MutationDetail::Base).encode(&seqs).unwrap();
+    assert!(default.slice(ndarray::s![.., 5..15]).iter().any(|v| *v != 0.0));
+
+    let codons: Vec<String> = ["ATG---", "ATGC--", "GTG---", "ATGAAA"].iter().map(|s| s.to_string()).collect();
+    let codon = CodonEncoder::new(0, CodonFeatures::Mutations);
+    assert!(codon.encode(&codons).unwrap().column(5).sum() > 0.0);
+    let x = codon.with_consensus(drop).encode(&codons).unwrap();
+    assert!(x.slice(ndarray::s![.., 3..6]).iter().all(|v| *v == 0.0));
+    assert_eq!(x.column(0).to_vec(), vec![0.0, 0.0, 1.0, 0.0]);
+
+    let aa = ColumnStats::new(&["D".to_string(), "N".to_string()], Alphabet::AminoAcid)
+        .unwrap()
+        .with_rules(ConsensusRules { ties: TieBreak::Ambiguity, ..Default::default() });
+    assert_eq!(aa.consensus(), "B");
+}
+
+#[test]
+fn explained_variance_sums_to_one() {
+    use pca_tree::PcaModel;
+
+    // variance 8/3 along x, 2/3 along y
+    let x = ndarray::array![[2.0f32, 0.0], [-2.0, 0.0], [0.0, 1.0], [0.0, -1.0]];
+    let mut pca = PcaModel::new(1);
+    pca.fit_transform(&x).unwrap();
+
+    assert_eq!(pca.eigenvalues.len(), 2);
+    assert!((pca.eigenvalues[0] - 8.0 / 3.0).abs() < 1e-4);
+    assert!((pca.total_variance() - 10.0 / 3.0).abs() < 1e-4);
+    assert_eq!(pca.explained_variance().len(), 1);
+    assert!((pca.explained_variance_ratio()[0] - 0.8).abs() < 1e-4);
+
+    let seqs: Vec<String> = ["ACGTAC", "ACGTTC", "TCGTAC", "ACCTAG", "GCGTAC"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let model = PcaTree::builder().components(30).fit(seqs).unwrap();
+    let cum = model.pca.cumulative_variance();
+    assert!(cum.windows(2).into_iter().all(|w| w[1] >= w[0] - 1e-6));
+    assert!((cum[cum.len() - 1] - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn component_selection_rules() {
+    use pca_tree::{ComponentSelection, PcaModel};
+
+    // two factors shared by columns 0-3 and 4-5, small noise on the other columns
+    let mut x = ndarray::Array2::<f32>::zeros((40, 8));
+    for i in 0..40 {
+        let f1 = if i % 2 == 0 { 1.5 } else { -1.5 };
+        let f2 = if i % 4 < 2 { 1.5 } else { -1.5 };
+        for j in 0..4 {
+            x[[i, j]] = f1;
+        }
+        x[[i, 4]] = f2;
+        x[[i, 5]] = f2;
+        for j in 6..8 {
+            x[[i, j]] = (((i * 7 + j * 13) % 11) as f32 - 5.0) * 0.05;
+        }
+    }
+
+    let fit = |rule| {
+        let mut pca = PcaModel::new(8).with_selection(rule);
+        pca.fit_transform(&x).unwrap();
+        pca.n_components()
+    };
+    assert_eq!(fit(ComponentSelection::Variance(0.5)), 1);
+    assert_eq!(fit(ComponentSelection::Variance(0.95)), 2);
+    assert_eq!(fit(ComponentSelection::BrokenStick), 2);
+    assert_eq!(fit(ComponentSelection::MarchenkoPastur), 2);
+    let parallel = ComponentSelection::ParallelAnalysis { permutations: 19, seed: 7 };
+    assert_eq!(fit(parallel), 2);
+
+    // same answer on the sparse path
+    let mut sparse = PcaModel::new(8).with_selection(parallel);
+    sparse.fit_transform_sparse(&pca_tree::CsrMatrix::from_dense(&x)).unwrap();
+    assert_eq!(sparse.n_components(), 2);
+    assert_eq!(sparse.coords().ncols(), 2);
+}
+
+#[test]
+fn new_sequences_project_into_fitted_space() {
+    use pca_tree::{Aligner, PcaTreeError};
+
+    let seqs: Vec<String> = ["ACGTACGTAC", "ACGTTCGTAC", "TCGTACGAAC", "ACGAACGTAC", "TCGTTCGAAG"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let unfitted = PcaTree::new(seqs.clone(), 2);
+    assert!(matches!(unfitted.transform(&seqs), Err(PcaTreeError::NotFitted)));
+
+    let model = PcaTree::builder().components(2).fit(seqs.clone()).unwrap();
+    let coords = model.coords().unwrap();
+
+    // a batch of one keeps the training consensus instead of becoming its own
+    let one = model.transform(&seqs[2..3]).unwrap();
+    for (a, b) in one.row(0).iter().zip(coords.row(2).iter()) {
+        assert!((a - b).abs() < 1e-5);
+    }
+    let nearest = model.nearest_nodes(&one).unwrap();
+    assert_eq!(nearest[0].0, 2);
+    assert!(nearest[0].1 < 1e-5);
+
+    assert_eq!(model.fitted_encoder().unwrap().feature_names(), model.features.as_slice());
+    assert!(matches!(
+        model.transform(&["ACGT".to_string()]),
+        Err(PcaTreeError::LengthMismatch { .. } | PcaTreeError::ReferenceLength { .. })
+    ));
+
+    // unaligned input lands in the training alignment's columns; the insertion is dropped
+    let reads: Vec<String> = ["ACGTACGTAC", "ACGTACGAC", "ACGTTACGTAC", "ACGTACGTAC"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let aligned = PcaTree::builder().align(Aligner::new()).components(2).fit(reads).unwrap();
+    assert_eq!(
+        aligned.fitted_encoder().unwrap().prepare(&["ACGTACGTAGAC".to_string()]),
+        vec!["ACG-TACGTAC".to_string()]
+    );
+    let x = aligned.transform(&["ACGTACGAC".to_string()]).unwrap();
+    let fitted = aligned.coords().unwrap();
+    for (a, b) in x.row(0).iter().zip(fitted.row(1).iter()) {
+        assert!((a - b).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn saved_model_loads_and_projects() {
+    use pca_tree::{Aligner, PcaTreeError};
+
+    let seqs: Vec<String> = ["ACGTACGTAC", "ACGTTCGTAC", "TCGTACGAAC", "ACGAACGTAC", "ACGTACGAC"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let model = PcaTree::builder()
+        .align(Aligner::new())
+        .germline("ACGTACGTAC")
+        .components(2)
+        .fit(seqs.clone())
+        .unwrap();
+
+    let dir = std::env::temp_dir().join("pca_tree_save_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["model.json", "model.json.gz"] {
+        model.save(dir.join(name)).unwrap();
+        let loaded = PcaTree::load(dir.join(name)).unwrap();
+
+        assert!(loaded.is_fitted() && loaded.has_germline());
+        assert_eq!(loaded.ids(), model.ids());
+        assert_eq!(loaded.tree(), model.tree());
+        assert_eq!(loaded.coords(), model.coords());
+        assert_eq!(loaded.pca.eigenvalues, model.pca.eigenvalues);
+        assert_eq!(loaded.transform(&seqs[1..3]).unwrap(), model.transform(&seqs[1..3]).unwrap());
+    }
+
+    std::fs::write(dir.join("other.json"), r#"{"format":"other","version":1}"#).unwrap();
+    assert!(matches!(PcaTree::load(dir.join("other.json")), Err(PcaTreeError::InvalidFormat(_))));
+    std::fs::write(dir.join("future.json"), r#"{"format":"pca_tree","version":999}"#).unwrap();
+    assert!(matches!(PcaTree::load(dir.join("future.json")), Err(PcaTreeError::InvalidFormat(_))));
+
+    // files whose parts disagree are rejected instead of failing later
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("model.json")).unwrap()).unwrap();
+    let edits: [fn(&mut serde_json::Value); 5] = [
+        |m| { m["ids"].as_array_mut().unwrap().pop(); },
+        |m| { m["counts"].as_array_mut().unwrap().pop(); },
+        |m| m["edges"][0][1] = 17.into(),
+        |m| { m["features"].as_array_mut().unwrap().pop(); },
+        |m| m["pca"]["eigenvalues"] = serde_json::json!({"v": 1, "dim": [0], "data": []}),
+    ];
+    for edit in edits {
+        let mut broken = json.clone();
+        edit(&mut broken);
+        std::fs::write(dir.join("broken.json"), broken.to_string()).unwrap();
+        match PcaTree::load(dir.join("broken.json")) {
+            Err(PcaTreeError::InvalidFormat(e)) => assert!(e.starts_with("Inconsistent model file"), "{e}"),
+            other => panic!("loaded a broken model: {:?}", other.map(|m| m.ids().len())),
+        }
+    }
+
+    let unfitted = PcaTree::new(seqs, 2);
+    assert!(matches!(unfitted.save(dir.join("unfitted.json")), Err(PcaTreeError::NotFitted)));
+}
+
+#[test]
+fn randomized_solver_matches_full() {
+    use pca_tree::{CsrMatrix, PcaModel, Solver, DEFAULT_SEED};
+
+    // three factors spread over 200 columns, plus small noise
+    let (n, p) = (60, 200);
+    let mut x = ndarray::Array2::<f32>::zeros((n, p));
+    for i in 0..n {
+        let factors = [((i % 5) as f32 - 2.0) * 2.0, ((i % 3) as f32 - 1.0) * 1.5, (i % 2) as f32];
+        for j in 0..p {
+            let noise = (((i * 31 + j * 17) % 23) as f32 - 11.0) * 0.002;
+            x[[i, j]] = factors[j % 3] * (1.0 + (j % 7) as f32 * 0.1) + noise;
+        }
+    }
+
+    let fit = |solver, seed| {
+        let mut pca = PcaModel::new(3).with_solver(solver).with_seed(seed);
+        pca.fit_transform(&x).unwrap();
+        pca
+    };
+    let full = fit(Solver::Full, 1);
+    let randomized = fit(Solver::Randomized, 1);
+    assert_eq!(full.eigenvalues.len(), p);
+    assert_eq!(randomized.eigenvalues.len(), 3);
+    assert!((full.total_variance() - randomized.total_variance()).abs() < 1e-2 * full.total_variance());
+
+    let close = |a: &ndarray::Array2<f32>, b: &ndarray::Array2<f32>| {
+        // components are only defined up to sign
+        a.columns().into_iter().zip(b.columns()).all(|(a, b)| {
+            let sign = if a.dot(&b) < 0.0 { -1.0 } else { 1.0 };
+            a.iter().zip(b.iter()).all(|(u, v)| (u - sign * v).abs() < 1e-2 * (1.0 + u.abs()))
+        })
+    };
+    for j in 0..3 {
+        let (f, r) = (full.eigenvalues[j], randomized.eigenvalues[j]);
+        assert!((f - r).abs() < 1e-3 * f, "eigenvalue {j}: {f} vs {r}");
+    }
+    assert!(close(full.coords(), randomized.coords()));
+
+    // same seed, same result; auto picks the randomized solver here
+    assert_eq!(fit(Solver::Randomized, 1).coords(), randomized.coords());
+    assert_eq!(fit(Solver::Auto, 1).eigenvalues.len(), 3);
+
+    // the model, the builder and the command line share one default seed
+    let mut unseeded = PcaModel::new(3).with_solver(Solver::Randomized);
+    unseeded.fit_transform(&x).unwrap();
+    assert_eq!(unseeded.seed, DEFAULT_SEED);
+    assert_eq!(fit(Solver::Randomized, DEFAULT_SEED).coords(), unseeded.coords());
+    let seqs: Vec<String> = ["ACGT", "ACGA", "TCGA"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(PcaTree::builder().components(2).fit(seqs).unwrap().pca.seed, DEFAULT_SEED);
+
+    let mut sparse = PcaModel::new(3).with_solver(Solver::Randomized).with_seed(1);
+    sparse.fit_transform_sparse(&CsrMatrix::from_dense(&x)).unwrap();
+    assert!(close(full.coords(), sparse.coords()));
+    assert!((full.total_variance() - sparse.total_variance()).abs() < 1e-2 * full.total_variance());
+}
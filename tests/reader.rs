@@ -0,0 +1,86 @@
+use pca_tree::parse_sequences;
+use std::io::Cursor;
+
+#[test]
+fn fasta_keeps_ids_and_joins_lines() {
+    let fa = ">seq1 some description\nACGT\nACGT\n>seq2\nTTTTAAAA\n";
+
+    let recs = parse_sequences(Cursor::new(fa)).unwrap();
+
+    assert_eq!(recs.len(), 2);
+    assert_eq!(recs[0].id, "seq1");
+    assert_eq!(recs[0].seq, "ACGTACGT");
+    assert_eq!(recs[1].id, "seq2");
+    assert!(recs[1].qual.is_none());
+}
+
+#[test]
+fn fastq_and_plain_input() {
+    let fq = "@r1\nACGT\n+\nIIII\n@r2\nACGA\n+\nII#I\n";
+    let recs = parse_sequences(Cursor::new(fq)).unwrap();
+    assert_eq!(recs[1].id, "r2");
+    assert_eq!(recs[1].qual.as_deref(), Some("II#I"));
+
+    // truncated record
+    assert!(parse_sequences(Cursor::new("@r1\nACGT\n+\n")).is_err());
+
+    // an empty read keeps its empty sequence and quality lines, also as the last record
+    let recs = parse_sequences(Cursor::new("@r1\n\n+\n\n@r2\nACGA\n+\nIIII\n@r3\n\n+\n\n\n")).unwrap();
+    let ids: Vec<&str> = recs.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, ["r1", "r2", "r3"]);
+    assert_eq!((recs[0].seq.as_str(), recs[0].qual.as_deref()), ("", Some("")));
+    assert_eq!(recs[1].seq, "ACGA");
+
+    // plain files are named by line index
+    let recs = parse_sequences(Cursor::new("ACGT\n\nACGA\n")).unwrap();
+    assert_eq!(recs[1].id, "1");
+}
+
+#[test]
+fn airr_table_grouped_by_clone() {
+    use pca_tree::{parse_airr, AirrOptions};
+
+    let tsv = "sequence_id\tclone_id\tcdr3\n\
+               a\t1\tACGT\n\
+               b\t2\tACGA\n\
+               c\t1\tACGG\n\
+               d\t2\t\n";
+
+    let opts = AirrOptions {
+        seq_column: "cdr3".to_string(),
+        group_column: Some("clone_id".to_string()),
+        ..Default::default()
+    };
+    let groups = parse_airr(Cursor::new(tsv), &opts).unwrap();
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].name, "1");
+    let ids: Vec<&str> = groups[0].records.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, ["a", "c"]);
+    // empty sequence is skipped
+    assert_eq!(groups[1].records.len(), 1);
+
+    let missing = AirrOptions { seq_column: "junction".to_string(), ..Default::default() };
+    assert!(parse_airr(Cursor::new(tsv), &missing).is_err());
+}
+
+#[test]
+fn airr_germline_column_per_group() {
+    use pca_tree::{parse_airr, AirrOptions};
+
+    let tsv = "sequence_id\tclone_id\tsequence_alignment\tgermline_alignment\n\
+               a\t1\tACGT\tACGA\n\
+               b\t2\tACGA\t\n\
+               c\t2\tACGG\tACCC\n";
+
+    let opts = AirrOptions {
+        group_column: Some("clone_id".to_string()),
+        germline_column: Some("germline_alignment".to_string()),
+        ..Default::default()
+    };
+    let groups = parse_airr(Cursor::new(tsv), &opts).unwrap();
+
+    assert_eq!(groups[0].germline.as_deref(), Some("ACGA"));
+    // first non-empty value wins
+    assert_eq!(groups[1].germline.as_deref(), Some("ACCC"));
+}